@@ -3,13 +3,350 @@ use near_sdk::collections::{LookupMap, Vector};
 use near_sdk::json_types::{Base64VecU8, U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, near_bindgen, AccountId, NearToken, Gas, Promise, PublicKey,
-    PanicOnDefault, log,
+    env, near_bindgen, AccountId, BorshStorageKey, CryptoHash, NearToken, Gas, Promise,
+    PromiseError, PromiseOrValue, PublicKey, PanicOnDefault, log,
 };
 
 // Gas constants
 const GAS_FOR_CROSS_CHAIN_CALL: Gas = Gas::from_tgas(100);
 const GAS_FOR_DEX_SWAP: Gas = Gas::from_tgas(150);
+const GAS_FOR_FT_TRANSFER: Gas = Gas::from_tgas(10);
+const GAS_FOR_SWAP_CALLBACK: Gas = Gas::from_tgas(30);
+const GAS_FOR_ORACLE_CALL: Gas = Gas::from_tgas(5);
+// Oracle and condition callbacks go on to schedule the DEX swap and its callback
+const GAS_FOR_ORACLE_CALLBACK: Gas = Gas::from_tgas(200);
+const GAS_FOR_CONDITION_CALL: Gas = Gas::from_tgas(5);
+const GAS_FOR_CONDITION_CALLBACK: Gas = Gas::from_tgas(200);
+const GAS_FOR_WITHDRAW_CALLBACK: Gas = Gas::from_tgas(10);
+
+// Longest oracle response read back, in bytes; enough for an `OraclePrice`
+const MAX_ORACLE_RESULT_LEN: usize = 256;
+
+// Longest external condition result read back, in bytes
+const MAX_CONDITION_RESULT_LEN: usize = 256;
+
+// Longest swap result read back, in bytes; enough for a JSON U128 string
+const MAX_SWAP_RESULT_LEN: usize = 64;
+
+// Longest payout result read back, in bytes; transfers and ft_transfer return nothing
+const MAX_PAYOUT_RESULT_LEN: usize = 16;
+
+// Protocol minimum gas price in yoctoNEAR per gas unit, used for cost estimates
+const GAS_PRICE_YOCTO: u128 = 100_000_000;
+
+// Storage layout version. Bump with each release that changes the fields of
+// `ArbitrageContract`; `OldArbitrageContract` is version 1.
+const STATE_VERSION: u32 = 2;
+
+// Smallest deposit v1.0.0 accepted per intent, in yoctoNEAR
+const V1_MIN_DEPOSIT: u128 = 1_000_000_000_000_000_000_000_000;
+
+// Fixed-point scale of the price diffs tracked in `pair_stats`
+const PRICE_DIFF_SCALE: f64 = 1_000_000.0;
+
+// Length of the window `max_executions_per_day` counts over
+const NANOS_PER_DAY: u64 = 86_400 * 1_000_000_000;
+
+// Curve byte of ED25519 public keys, the only curve `env::ed25519_verify` checks
+const CURVE_TYPE_ED25519: u8 = 0;
+
+// Most signatures `verify_cross_chain_signatures_batch` checks in one call
+const MAX_SIGNATURE_BATCH: usize = 50;
+
+// Longest intent label accepted, in bytes
+const MAX_LABEL_LEN: usize = 64;
+
+// Highest min_profit_threshold (in percent) accepted until the owner changes it
+const DEFAULT_MAX_PROFIT_THRESHOLD: f64 = 100.0;
+
+// NEP-297 event metadata
+const EVENT_STANDARD: &str = "arbitrage";
+const EVENT_VERSION: &str = "1.0.0";
+
+/// Storage prefix of every collection. Each variant serializes to a distinct
+/// one-byte tag, and per-account or per-intent `Vector`s append a fixed-size
+/// hash of their id, so no prefix is a prefix of another. The tags also stay
+/// below the ASCII byte strings that collections migrated from the old layout
+/// keep using. Only append variants: reordering changes stored prefixes.
+#[derive(BorshStorageKey, BorshSerialize)]
+pub enum StorageKey {
+    Intents,
+    UserIntents,
+    Executions,
+    UserExecutions,
+    UserProfits,
+    CrossChainSignatures,
+    TotalDeposited,
+    ExecutedKeys,
+    IntentExecutions,
+    ClaimableProfits,
+    UserProfitTokens,
+    UserExecutionNonces,
+    AllIntentIds,
+    ReferrerEarnings,
+    SupportedChains,
+    LegacyUserProfits,
+    AllExecutionIds,
+    PairsSeen,
+    UserDefaults,
+    DailyExecutions,
+    UserEarnedTokens,
+    PairStats,
+    ProfitEpochs,
+    ProfitEpochCount,
+    PkIntents,
+    Templates,
+    CancelledIntents,
+    PairMinThreshold,
+    Roles,
+    Blocklist,
+    UserIntentList { account_hash: CryptoHash },
+    UserExecutionList { account_hash: CryptoHash },
+    IntentExecutionList { intent_hash: CryptoHash },
+    UserProfitTokenList { account_hash: CryptoHash },
+    UserEarnedTokenList { account_hash: CryptoHash },
+    PkIntentList { key_hash: CryptoHash },
+    TokenCreationFees,
+    MergedIntents,
+}
+
+/// Reference id recorded as an execution's `tx_hash`. Contracts can't read
+/// the hash of the transaction they run in, so this pseudo-hash is the hex
+/// sha256 of the signer's public key, the block timestamp and a monotonic
+/// execution counter: deterministic for a given call, unique per execution.
+fn pseudo_tx_hash(counter: u64) -> String {
+    let preimage = borsh::to_vec(&(env::signer_account_pk(), env::block_timestamp(), counter))
+        .expect("Failed to serialize tx hash preimage");
+    hex::encode(env::sha256(&preimage))
+}
+
+/// Message a cross-chain signature signs: the borsh encoding of
+/// `(execution_id, chain_id, nonce)`.
+fn signature_message(execution_id: &str, chain_id: u64, nonce: u64) -> Vec<u8> {
+    borsh::to_vec(&(execution_id, chain_id, nonce)).expect("Failed to serialize signature message")
+}
+
+/// Logs a NEP-297 event so indexers and relayers can subscribe instead of polling.
+fn emit_event(event: &str, data: serde_json::Value) {
+    log!(
+        "EVENT_JSON:{}",
+        serde_json::json!({
+            "standard": EVENT_STANDARD,
+            "version": EVENT_VERSION,
+            "event": event,
+            "data": [data],
+        })
+    );
+}
+
+fn assert_valid_label(label: &Option<String>) {
+    if let Some(label) = label {
+        assert!(
+            label.len() <= MAX_LABEL_LEN,
+            "Label exceeds {} bytes",
+            MAX_LABEL_LEN
+        );
+    }
+}
+
+/// Requires a `BASE/QUOTE` pair of non-empty alphanumeric symbols.
+fn assert_valid_token_pair(token_pair: &str) {
+    let valid = match token_pair.split_once('/') {
+        Some((base, quote)) => [base, quote]
+            .iter()
+            .all(|symbol| !symbol.is_empty() && symbol.chars().all(|c| c.is_ascii_alphanumeric())),
+        None => false,
+    };
+    assert!(valid, "Invalid token_pair: expected BASE/QUOTE");
+}
+
+/// Orders the two symbols of a BASE/QUOTE pair alphabetically, so that
+/// "USDC/ETH" and "ETH/USDC" map to the same market.
+fn normalize_pair(token_pair: &str) -> String {
+    match token_pair.split_once('/') {
+        Some((base, quote)) if quote < base => format!("{}/{}", quote, base),
+        _ => token_pair.to_string(),
+    }
+}
+
+/// Parses a min_profit_threshold, which must be a finite positive percentage.
+fn parse_threshold(threshold: &str) -> f64 {
+    let threshold: f64 = threshold.parse().unwrap_or_else(|_| {
+        env::panic_str("Invalid min_profit_threshold: must be a valid number")
+    });
+    assert!(
+        threshold.is_finite() && threshold > 0.0,
+        "min_profit_threshold must be a finite positive number"
+    );
+    threshold
+}
+
+/// Parses a min_price_diff, an absolute price gap that must be finite and positive.
+fn parse_min_price_diff(min_price_diff: &str) -> f64 {
+    let min_price_diff: f64 = min_price_diff.parse().unwrap_or_else(|_| {
+        env::panic_str("Invalid min_price_diff: must be a valid number")
+    });
+    assert!(
+        min_price_diff.is_finite() && min_price_diff > 0.0,
+        "min_price_diff must be a finite positive number"
+    );
+    min_price_diff
+}
+
+/// Parses both quoted prices, rejecting values the profit math can't use.
+fn try_parse_prices(near_price: &str, eth_price: &str) -> Result<(f64, f64), String> {
+    let near_price: f64 = near_price
+        .parse()
+        .map_err(|_| "Invalid near_price: must be a valid number".to_string())?;
+    let eth_price: f64 = eth_price
+        .parse()
+        .map_err(|_| "Invalid eth_price: must be a valid number".to_string())?;
+    // NaN compares false against every threshold, so it would slip through
+    if !near_price.is_finite() || !eth_price.is_finite() {
+        return Err("Prices must be finite numbers".to_string());
+    }
+    // Reject before dividing by the smaller price, which must be non-zero
+    if near_price <= 0.0 || eth_price <= 0.0 {
+        return Err("Prices must be positive".to_string());
+    }
+    if near_price == eth_price {
+        return Err("Prices are equal: no spread to arbitrage".to_string());
+    }
+    Ok((near_price, eth_price))
+}
+
+fn parse_prices(near_price: &str, eth_price: &str) -> (f64, f64) {
+    try_parse_prices(near_price, eth_price).unwrap_or_else(|err| env::panic_str(&err))
+}
+
+/// Spread between the two prices as a percentage of the smaller one.
+fn profit_percentage(near_price: f64, eth_price: f64) -> f64 {
+    let price_diff = (near_price - eth_price).abs();
+    (price_diff / near_price.min(eth_price)) * 100.0
+}
+
+/// Profit for executing `amount` of an intent's notional: `profit_factor_bps`
+/// of the price difference, scaled by the share of the position executed and
+/// capped at `amount` itself, so quoted prices can't claim more than the
+/// notional put to work.
+fn execution_profit(
+    intent: &ArbitrageIntent,
+    near_price: f64,
+    eth_price: f64,
+    amount: u128,
+    profit_factor_bps: u16,
+) -> f64 {
+    let price_diff = (near_price - eth_price).abs();
+    let profit_factor = profit_factor_bps as f64 / 10_000.0;
+    (price_diff * profit_factor * (amount as f64 / intent.deposit.0 as f64)).min(to_near(amount))
+}
+
+/// Sends `amount` of `token_id` to `receiver`: a plain transfer when the
+/// token is the contract's own id (native NEAR), `ft_transfer` otherwise.
+fn pay_out(token_id: &AccountId, receiver: AccountId, amount: u128) -> Promise {
+    if *token_id == env::current_account_id() {
+        return Promise::new(receiver).transfer(NearToken::from_yoctonear(amount));
+    }
+    Promise::new(token_id.clone()).function_call(
+        "ft_transfer".to_string(),
+        serde_json::json!({ "receiver_id": receiver, "amount": U128(amount) })
+            .to_string()
+            .into_bytes(),
+        NearToken::from_yoctonear(1),
+        GAS_FOR_FT_TRANSFER,
+    )
+}
+
+/// Median of a non-empty set of prices; the mean of the middle two for an
+/// even count.
+fn median(prices: &mut [f64]) -> f64 {
+    prices.sort_by(|a, b| a.total_cmp(b));
+    let middle = prices.len() / 2;
+    if prices.len().is_multiple_of(2) {
+        (prices[middle - 1] + prices[middle]) / 2.0
+    } else {
+        prices[middle]
+    }
+}
+
+/// Converts a whole-NEAR amount to yoctoNEAR.
+fn to_yocto(amount: f64) -> u128 {
+    (amount * 1_000_000_000_000_000_000_000_000.0) as u128
+}
+
+/// Converts a yoctoNEAR amount to whole NEAR.
+fn to_near(amount: u128) -> f64 {
+    amount as f64 / 1_000_000_000_000_000_000_000_000.0
+}
+
+/// Reads the output amount a DEX swap returned, as a JSON `U128` string or a
+/// borsh `u128`.
+fn parse_swap_output(data: &[u8]) -> Option<u128> {
+    serde_json::from_slice::<U128>(data)
+        .map(|output| output.0)
+        .ok()
+        .or_else(|| u128::try_from_slice(data).ok())
+}
+
+/// Maps a v1 intent onto the current layout. v1 didn't record the NEAR
+/// attached to an intent, so it is credited with `V1_MIN_DEPOSIT`, all of it
+/// executed if the intent was. The creating key is unknown as well, so the
+/// intent has no `signer_pk` and is left out of `pk_intents`.
+fn migrate_intent(old: OldArbitrageIntent) -> ArbitrageIntent {
+    let executed = matches!(old.status, IntentStatus::Executed);
+    ArbitrageIntent {
+        id: old.id,
+        user: old.user,
+        token_pair: old.token_pair,
+        min_profit_threshold: old.min_profit_threshold,
+        status: old.status,
+        created_at: old.created_at,
+        funding_token: None,
+        deposit: U128(V1_MIN_DEPOSIT),
+        executed_amount: U128(if executed { V1_MIN_DEPOSIT } else { 0 }),
+        admin_locked: false,
+        authorized_keepers: Vec::new(),
+        label: None,
+        last_executed_at: U64(0),
+        min_absolute_profit: None,
+        referrer: None,
+        activate_at: None,
+        expires_at: None,
+        keeper_fee_bps: 0,
+        consecutive_failures: 0,
+        dex: None,
+        min_price_diff: None,
+        signer_pk: None,
+        max_executions: None,
+        execution_count: if executed { 1 } else { 0 },
+        condition: None,
+        kind: IntentKind::Spread,
+        paused_at: None,
+        interval_ns: None,
+        owner_only_execution: true,
+        strict_threshold: false,
+        pending_swaps: 0,
+    }
+}
+
+/// Maps a v1 execution onto the current layout. v1 executions always filled
+/// the whole intent, so their amount is the deposit `migrate_intent` credits.
+fn migrate_execution(old: OldArbitrageExecution) -> ArbitrageExecution {
+    ArbitrageExecution {
+        id: old.id,
+        intent_id: old.intent_id,
+        user: old.user,
+        token_pair: old.token_pair,
+        price_diff: old.price_diff,
+        profit: old.profit,
+        gas_fees: old.gas_fees,
+        tx_hash: old.tx_hash,
+        timestamp: old.timestamp,
+        near_price: old.near_price,
+        eth_price: old.eth_price,
+        amount: U128(V1_MIN_DEPOSIT),
+    }
+}
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
@@ -20,16 +357,166 @@ pub struct ArbitrageIntent {
     pub min_profit_threshold: f64,
     pub status: IntentStatus,
     pub created_at: U64,
+    /// NEP-141 token the intent was funded with, or `None` for native NEAR.
+    pub funding_token: Option<AccountId>,
+    pub deposit: U128,
+    /// Notional already executed; the intent completes once this reaches `deposit`.
+    pub executed_amount: U128,
+    /// Set by the contract owner to stop the intent owner from resuming it.
+    pub admin_locked: bool,
+    /// Accounts allowed to call `execute_arbitrage` on the owner's behalf.
+    pub authorized_keepers: Vec<AccountId>,
+    pub label: Option<String>,
+    /// Block timestamp of the latest execution, 0 until first executed.
+    pub last_executed_at: U64,
+    /// Smallest profit (yoctoNEAR) worth executing for, checked alongside the percentage threshold.
+    pub min_absolute_profit: Option<U128>,
+    /// Account that referred the intent owner; earns `referrer_bps` of each execution's profit.
+    pub referrer: Option<AccountId>,
+    /// Block timestamp before which the intent cannot execute, even while `Active`.
+    pub activate_at: Option<U64>,
+    /// Block timestamp from which the intent can no longer execute or be resumed.
+    pub expires_at: Option<U64>,
+    /// Share of the owner's profit paid to an authorized keeper that executes the intent.
+    pub keeper_fee_bps: u16,
+    /// Failed swaps since the last successful one.
+    pub consecutive_failures: u32,
+    /// DEX this intent swaps through, overriding `dex_contract`.
+    pub dex: Option<AccountId>,
+    /// Smallest absolute price gap an execution needs, on top of the threshold.
+    pub min_price_diff: Option<f64>,
+    /// Key that signed the transaction creating the intent; `None` for
+    /// intents migrated from v1, which didn't record it.
+    pub signer_pk: Option<PublicKey>,
+    /// Successful executions allowed before the intent stops executing.
+    pub max_executions: Option<u32>,
+    /// Executions whose swap succeeded or is still in flight.
+    pub execution_count: u32,
+    pub condition: Option<ExternalCondition>,
+    pub kind: IntentKind,
+    /// Block timestamp the intent was last paused at, while it is paused.
+    pub paused_at: Option<U64>,
+    /// Least time between executions, for intents run on a fixed cadence.
+    pub interval_ns: Option<U64>,
+    /// Only the intent owner can execute while set, whatever `authorized_keepers` holds.
+    pub owner_only_execution: bool,
+    /// Require profit strictly above `min_profit_threshold` rather than at least equal.
+    pub strict_threshold: bool,
+    /// Executions whose swap callback hasn't resolved yet; the intent can't be
+    /// cancelled until they settle.
+    pub pending_swaps: u32,
+}
+
+impl ArbitrageIntent {
+    /// Whether these prices meet the intent's trigger: its percentage
+    /// threshold, or for limit orders the target price crossing.
+    fn check_trigger(&self, near_price: f64, eth_price: f64) -> Result<(), String> {
+        match &self.kind {
+            IntentKind::Spread => {
+                let profit = profit_percentage(near_price, eth_price);
+                if profit < self.min_profit_threshold {
+                    return Err("Profit below threshold".to_string());
+                }
+                if self.strict_threshold && profit == self.min_profit_threshold {
+                    return Err("Profit not above threshold".to_string());
+                }
+            }
+            IntentKind::LimitOrder { target_price, direction } => {
+                let crossed = match direction {
+                    PriceDirection::Above => near_price >= *target_price,
+                    PriceDirection::Below => near_price <= *target_price,
+                };
+                if !crossed {
+                    return Err("Target price not reached".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn can_execute(&self, account: &AccountId) -> bool {
+        self.user == *account
+            || (!self.owner_only_execution && self.authorized_keepers.contains(account))
+    }
+
+    /// Whether the intent's scheduled activation time, if any, has passed.
+    fn is_activated(&self) -> bool {
+        self.activate_at
+            .is_none_or(|activate_at| env::block_timestamp() >= activate_at.0)
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| env::block_timestamp() >= expires_at.0)
+    }
+}
+
+/// Side of the target price a limit order waits for near_price to reach.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum PriceDirection {
+    Above,
+    Below,
+}
+
+/// What makes an intent executable.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum IntentKind {
+    /// The spread between the two prices reaches `min_profit_threshold`.
+    Spread,
+    /// near_price reaches `target_price` from the given direction; the
+    /// percentage threshold is not checked.
+    LimitOrder { target_price: f64, direction: PriceDirection },
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
-#[serde(crate = "near_sdk::serde")]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
 pub enum IntentStatus {
     Active,
     Paused,
     Executed,
 }
 
+/// Privileges the owner can delegate. An account's roles are stored as a bit
+/// mask of `Role::bit`; the owner implicitly holds all of them. Granting and
+/// revoking roles and `owner_emergency_withdraw` stay with the owner alone.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum Role {
+    /// Operational settings: pairs, tokens, chains, limits, reserve ratio,
+    /// oracles, DEX, intent locks, the blocklist, stats and record upkeep
+    /// (`recompute_pair_stats`, `reset_profit_counter`, `migrate_records`).
+    Admin,
+    /// Fee and profit-share settings, and the treasury account.
+    FeeManager,
+    /// Pausing the contract and individual intents.
+    Pauser,
+}
+
+impl Role {
+    const ALL: [Role; 3] = [Role::Admin, Role::FeeManager, Role::Pauser];
+
+    fn bit(self) -> u8 {
+        match self {
+            Role::Admin => 1,
+            Role::FeeManager => 1 << 1,
+            Role::Pauser => 1 << 2,
+        }
+    }
+}
+
+impl IntentStatus {
+    /// Same string the status serializes to in view responses.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IntentStatus::Active => "active",
+            IntentStatus::Paused => "paused",
+            IntentStatus::Executed => "executed",
+        }
+    }
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct ArbitrageExecution {
@@ -40,10 +527,182 @@ pub struct ArbitrageExecution {
     pub price_diff: f64,
     pub profit: f64,
     pub gas_fees: f64,
+    /// Pseudo-hash from `pseudo_tx_hash`, not the hash of a real transaction.
     pub tx_hash: String,
     pub timestamp: U64,
     pub near_price: f64,
     pub eth_price: f64,
+    pub amount: U128,
+}
+
+/// An execution with its profit broken down to what the user nets, in
+/// yoctoNEAR.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ExecutionNetView {
+    #[serde(flatten)]
+    pub execution: ArbitrageExecution,
+    /// Contract fee on the gross profit, at the current `fee_basis_points`.
+    pub fee: U128,
+    /// `profit - gas_fees - fee`, floored at zero.
+    pub net_profit: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GlobalStats {
+    pub total_intents: u64,
+    pub total_executions: u64,
+    pub total_profit_paid: U128,
+    pub total_volume: U128,
+}
+
+/// Every fee setting of the contract, for the fee dashboard.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeeConfig {
+    pub fee_basis_points: u16,
+    pub referrer_bps: u16,
+    /// Default keeper fee of new intents.
+    pub keeper_fee_bps: u16,
+    pub cancellation_fee_bps: u16,
+    /// Non-refundable NEAR charged per new intent.
+    pub creation_fee: U128,
+    pub treasury: AccountId,
+}
+
+/// One page of a user's intents and executions, for off-chain backups.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UserExport {
+    pub intents: Vec<ArbitrageIntent>,
+    pub executions: Vec<ArbitrageExecution>,
+    /// Realized profit in NEAR, as returned by `get_total_profit`.
+    pub total_profit: U128,
+}
+
+/// Running statistics of the price diffs executed on one pair, updated with
+/// Welford's algorithm. Diffs are scaled by `PRICE_DIFF_SCALE` to integers.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct PairStats {
+    pub count: u64,
+    pub mean: i128,
+    /// Sum of squared deviations from the mean.
+    pub m2: i128,
+}
+
+impl PairStats {
+    fn record(&mut self, scaled_diff: i128) {
+        self.count += 1;
+        let delta = scaled_diff - self.mean;
+        self.mean += delta / self.count as i128;
+        self.m2 += delta * (scaled_diff - self.mean);
+    }
+}
+
+/// Sizes of the global intent and execution lists paged by `dump_intents`
+/// and `dump_executions`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DumpCursors {
+    pub total_intents: u64,
+    pub total_executions: u64,
+}
+
+/// How the contract's NEAR balance is committed, in yoctoNEAR.
+/// `reserved_profits + treasury_earnings + intent_deposits + storage_reserved
+/// + free` adds up to `total`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BalanceBreakdown {
+    pub total: U128,
+    pub reserved_profits: U128,
+    pub treasury_earnings: U128,
+    pub intent_deposits: U128,
+    pub storage_reserved: U128,
+    pub free: U128,
+}
+
+/// A quote returned by an oracle's `get_price`: both legs of the pair and
+/// the time they were observed, in nanoseconds.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OraclePrice {
+    pub near_price: String,
+    pub eth_price: String,
+    pub timestamp: U64,
+}
+
+/// One entry of `execute_arbitrage_batch`, quoted and keyed like an
+/// `execute_arbitrage` call.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde", deny_unknown_fields)]
+pub struct BatchExecution {
+    pub intent_id: String,
+    pub near_price: String,
+    pub eth_price: String,
+    pub price_timestamp: U64,
+    pub idempotency_key: Option<String>,
+}
+
+/// User-supplied settings of a new intent. Built from `create_intent`'s
+/// arguments, or decoded from the `msg` of an `ft_transfer_call`. Unknown
+/// fields are rejected so a misspelt setting fails instead of being dropped.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde", deny_unknown_fields)]
+pub struct IntentParams {
+    pub token_pair: String,
+    /// Falls back to the user's default threshold when omitted.
+    pub min_profit_threshold: Option<String>,
+    pub label: Option<String>,
+    pub min_absolute_profit: Option<U128>,
+    pub referrer: Option<AccountId>,
+    pub activate_at: Option<U64>,
+    pub expires_at: Option<U64>,
+    /// Routes this intent's swaps through another DEX than the global default.
+    pub dex: Option<AccountId>,
+    /// Smallest absolute gap between the two prices worth executing on.
+    pub min_price_diff: Option<String>,
+    /// Successful executions after which the intent stops executing.
+    pub max_executions: Option<u32>,
+    /// External contract state that must hold for the intent to execute.
+    pub condition: Option<ExternalCondition>,
+    /// Makes the intent a limit order on near_price; needs `direction`.
+    pub target_price: Option<String>,
+    pub direction: Option<PriceDirection>,
+}
+
+/// View call whose result must equal `expected` before an intent executes.
+/// `method` is called without arguments. A JSON string result is compared
+/// with `expected` as is, any other JSON value with `expected` parsed as JSON
+/// (e.g. "true", "42").
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ExternalCondition {
+    pub contract: AccountId,
+    pub method: String,
+    pub expected: String,
+}
+
+impl ExternalCondition {
+    fn is_met(&self, result: &[u8]) -> bool {
+        match serde_json::from_slice::<serde_json::Value>(result) {
+            Ok(serde_json::Value::String(value)) => value == self.expected,
+            Ok(value) => serde_json::from_str::<serde_json::Value>(&self.expected)
+                .is_ok_and(|expected| expected == value),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Owner-registered parameter set that `create_intent_from_template`
+/// instantiates.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct IntentTemplate {
+    pub token_pair: String,
+    pub min_profit_threshold: String,
+    pub max_executions: Option<u32>,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
@@ -55,6 +714,16 @@ pub struct CrossChainSignature {
     pub nonce: u64,
 }
 
+/// JSON form of a stored `CrossChainSignature` for relayers to read back.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CrossChainSignatureView {
+    pub signature: Base64VecU8,
+    pub public_key: String,
+    pub chain_id: u64,
+    pub nonce: u64,
+}
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct ArbitrageContract {
@@ -63,69 +732,667 @@ pub struct ArbitrageContract {
     pub user_intents: LookupMap<AccountId, Vector<String>>,
     pub executions: LookupMap<String, ArbitrageExecution>,
     pub user_executions: LookupMap<AccountId, Vector<String>>,
+    /// Lifetime profit keyed by (user, token); native NEAR uses the contract's own id.
+    pub user_profits: LookupMap<(AccountId, AccountId), U128>,
+    pub next_intent_id: u64,
+    pub next_execution_id: u64,
+    pub cross_chain_signatures: LookupMap<String, CrossChainSignature>,
+    pub total_deposited: LookupMap<AccountId, U128>,
+    pub executed_keys: LookupMap<String, String>,
+    pub max_profit_threshold: f64,
+    pub intent_executions: LookupMap<String, Vector<String>>,
+    pub state_version: u32,
+    /// Withdrawable profit keyed by (user, token); native NEAR uses the contract's own id.
+    pub claimable_profits: LookupMap<(AccountId, AccountId), U128>,
+    pub user_profit_tokens: LookupMap<AccountId, Vector<AccountId>>,
+    pub total_profit_paid: U128,
+    /// Sum of executed notional across all intents.
+    pub total_volume: U128,
+    /// Per-user counter mixed into execution ids so they stay unique within a block.
+    pub user_execution_nonces: LookupMap<AccountId, u64>,
+    /// Native NEAR owed to users as claimable profit; off-limits to the owner.
+    pub reserved_profits: U128,
+    /// Minimum time between two executions of the same intent.
+    pub execution_cooldown_ns: U64,
+    /// Every intent id in creation order, for index-based enumeration.
+    pub all_intent_ids: Vector<String>,
+    /// Share of each execution's profit paid to the treasury, in basis points.
+    pub fee_basis_points: u16,
+    /// Account that collects protocol fees; the owner unless changed.
+    pub treasury: AccountId,
+    pub treasury_earnings: U128,
+    /// Share of each execution's profit paid to the intent's referrer, in basis points.
+    pub referrer_bps: u16,
+    pub referrer_earnings: LookupMap<AccountId, U128>,
+    /// Chains `store_cross_chain_signature` accepts signatures for.
+    pub supported_chains: LookupMap<u64, bool>,
+    /// Keys of `supported_chains`, for enumeration.
+    pub supported_chain_ids: Vec<u64>,
+    /// NEAR profit recorded before profits were tracked per token; counted as native profit.
+    pub legacy_user_profits: LookupMap<AccountId, U128>,
+    /// Oldest quote `execute_arbitrage` accepts, by `price_timestamp`; 0 disables the check.
+    pub max_price_age_ns: U64,
+    /// Every execution id in execution order, for the protocol-wide activity feed.
+    pub all_execution_ids: Vector<String>,
+    /// Token pairs intents may trade; empty allows any well-formed pair.
+    pub allowed_pairs: Vec<String>,
+    /// NEAR paid on top of profit for the first settled execution on each pair.
+    pub first_execution_bonus: U128,
+    pub pairs_seen: LookupMap<String, bool>,
+    /// Public key curve bytes `store_cross_chain_signature` accepts.
+    pub allowed_curve_types: Vec<u8>,
+    /// Per-user min_profit_threshold used when `create_intent` is given none.
+    pub user_defaults: LookupMap<AccountId, String>,
+    /// Stops new intents, executions and withdrawals while set.
+    pub paused: bool,
+    /// DEX that execution swaps are routed to, once configured.
+    pub dex_contract: Option<AccountId>,
+    /// Price oracles trusted for execution prices.
+    pub oracle_accounts: Vec<AccountId>,
+    /// Failed swaps in a row after which an intent pauses itself; 0 disables.
+    pub max_consecutive_failures: u32,
+    /// Executions one account may submit per day; 0 disables the limit.
+    pub max_executions_per_day: u32,
+    /// Executions submitted per `(account, day index)`.
+    pub daily_executions: LookupMap<(AccountId, u64), u32>,
+    /// Oracle responses needed before an oracle-priced execution proceeds.
+    pub oracle_quorum: u32,
+    /// Every token each user has realized profit in, to enumerate `user_profits`.
+    pub user_earned_tokens: LookupMap<AccountId, Vector<AccountId>>,
+    /// Share of a cancelled intent's unexecuted deposit kept by the treasury, in basis points.
+    pub cancellation_fee_bps: u16,
+    /// Active or paused intents one user may hold at once; 0 disables the limit.
+    pub max_active_intents_per_user: u32,
+    /// Price diff statistics per normalized pair.
+    pub pair_stats: LookupMap<String, PairStats>,
+    /// NEAR profit counters archived by `reset_profit_counter`, per user and epoch.
+    pub profit_epochs: LookupMap<(AccountId, u32), U128>,
+    /// Number of profit epochs closed per user.
+    pub profit_epoch_count: LookupMap<AccountId, u32>,
+    /// Intent ids by the public key that signed their creation.
+    pub pk_intents: LookupMap<PublicKey, Vector<String>>,
+    /// Share of the price difference paid out as profit, in basis points.
+    pub profit_factor_bps: u16,
+    /// Intent templates by name.
+    pub templates: LookupMap<String, IntentTemplate>,
+    /// Native NEAR withdrawn per window above which the contract pauses itself; 0 disables.
+    pub circuit_breaker_threshold: U128,
+    /// Start of the current outflow window.
+    pub outflow_window_start: U64,
+    /// Native NEAR withdrawn since `outflow_window_start`.
+    pub outflow_window_amount: U128,
+    /// Keeper fee new intents start with; intent owners may change it per intent.
+    pub default_keeper_fee_bps: u16,
+    /// Cancellation time of every cancelled intent, so lookups can tell it from an unknown id.
+    pub cancelled_intents: LookupMap<String, U64>,
+    /// Lowest min_profit_threshold accepted per normalized pair.
+    pub pair_min_threshold: LookupMap<String, String>,
+    /// Roles granted to accounts other than the owner, as `Role::bit` masks.
+    pub roles: LookupMap<AccountId, u8>,
+    /// Accounts barred from creating intents, executing and withdrawing profit.
+    pub blocklist: LookupMap<AccountId, bool>,
+    /// Refundable part of the NEAR attached to a new intent, which becomes its deposit.
+    pub storage_deposit: U128,
+    /// Non-refundable NEAR charged per new intent and credited to the treasury.
+    pub creation_fee: U128,
+    /// Share of the tracked NEAR liabilities the balance must still cover
+    /// after a withdrawal, in basis points; 0 disables the check.
+    pub reserve_ratio_bps: u16,
+    /// How close to expiry, or how long paused or unexecuted, an intent must be
+    /// before `get_intents_needing_attention` reports it.
+    pub attention_window_ns: U64,
+    /// Unexecuted notional of native NEAR intents, refundable through
+    /// `cancel_intent` and so off-limits to the owner.
+    pub intent_deposits: U128,
+    /// NEP-141 tokens `ft_on_transfer` accepts, each with the creation fee it
+    /// charges per intent in that token's own units.
+    pub token_creation_fees: LookupMap<AccountId, U128>,
+    /// Keys of `token_creation_fees`, for enumeration.
+    pub allowed_token_ids: Vec<AccountId>,
+    /// Executions whose swap settled; failed swaps keep their record and id
+    /// but aren't counted.
+    pub settled_executions: u64,
+    /// Id each merged intent was folded into, so lookups can tell it from an unknown id.
+    pub merged_intents: LookupMap<String, String>,
+    /// v1 records `migrate_records` has yet to rewrite; `None` once done.
+    pub migration_cursor: Option<MigrationCursor>,
+}
+
+/// Progress of `migrate_records` through the ids v1 assigned. The ends are
+/// fixed at `migrate` time so that records created afterwards are never
+/// read in the v1 layout.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct MigrationCursor {
+    pub next_intent_id: u64,
+    pub intent_end: u64,
+    pub next_execution_id: u64,
+    pub execution_end: u64,
+}
+
+/// Storage layout of `ArbitrageContract` as originally deployed (v1.0.0).
+/// `migrate` reads state in this shape and maps it onto the current layout,
+/// so keep it frozen even as fields are added to `ArbitrageContract`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldArbitrageContract {
+    pub owner: AccountId,
+    pub intents: LookupMap<String, OldArbitrageIntent>,
+    pub user_intents: LookupMap<AccountId, Vector<String>>,
+    pub executions: LookupMap<String, OldArbitrageExecution>,
+    pub user_executions: LookupMap<AccountId, Vector<String>>,
     pub user_profits: LookupMap<AccountId, U128>,
     pub next_intent_id: u64,
     pub next_execution_id: u64,
     pub cross_chain_signatures: LookupMap<String, CrossChainSignature>,
 }
 
+/// `ArbitrageIntent` as stored by v1.0.0. Frozen like `OldArbitrageContract`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldArbitrageIntent {
+    pub id: String,
+    pub user: AccountId,
+    pub token_pair: String,
+    pub min_profit_threshold: f64,
+    pub status: IntentStatus,
+    pub created_at: U64,
+}
+
+/// `ArbitrageExecution` as stored by v1.0.0. Frozen like `OldArbitrageContract`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldArbitrageExecution {
+    pub id: String,
+    pub intent_id: String,
+    pub user: AccountId,
+    pub token_pair: String,
+    pub price_diff: f64,
+    pub profit: f64,
+    pub gas_fees: f64,
+    pub tx_hash: String,
+    pub timestamp: U64,
+    pub near_price: f64,
+    pub eth_price: f64,
+}
+
 #[near_bindgen]
 impl ArbitrageContract {
     #[init]
     pub fn new(owner: AccountId) -> Self {
         assert!(!env::state_exists(), "Already initialized");
         Self {
-            owner,
-            intents: LookupMap::new(b"intents".to_vec()),
-            user_intents: LookupMap::new(b"user_intents".to_vec()),
-            executions: LookupMap::new(b"executions".to_vec()),
-            user_executions: LookupMap::new(b"user_executions".to_vec()),
-            user_profits: LookupMap::new(b"user_profits".to_vec()),
+            owner: owner.clone(),
+            intents: LookupMap::new(StorageKey::Intents),
+            user_intents: LookupMap::new(StorageKey::UserIntents),
+            executions: LookupMap::new(StorageKey::Executions),
+            user_executions: LookupMap::new(StorageKey::UserExecutions),
+            user_profits: LookupMap::new(StorageKey::UserProfits),
             next_intent_id: 1,
             next_execution_id: 1,
-            cross_chain_signatures: LookupMap::new(b"cross_chain_sigs".to_vec()),
+            cross_chain_signatures: LookupMap::new(StorageKey::CrossChainSignatures),
+            total_deposited: LookupMap::new(StorageKey::TotalDeposited),
+            executed_keys: LookupMap::new(StorageKey::ExecutedKeys),
+            max_profit_threshold: DEFAULT_MAX_PROFIT_THRESHOLD,
+            intent_executions: LookupMap::new(StorageKey::IntentExecutions),
+            state_version: STATE_VERSION,
+            claimable_profits: LookupMap::new(StorageKey::ClaimableProfits),
+            user_profit_tokens: LookupMap::new(StorageKey::UserProfitTokens),
+            total_profit_paid: U128(0),
+            total_volume: U128(0),
+            user_execution_nonces: LookupMap::new(StorageKey::UserExecutionNonces),
+            reserved_profits: U128(0),
+            execution_cooldown_ns: U64(0),
+            all_intent_ids: Vector::new(StorageKey::AllIntentIds),
+            fee_basis_points: 0,
+            treasury: owner,
+            treasury_earnings: U128(0),
+            referrer_bps: 0,
+            referrer_earnings: LookupMap::new(StorageKey::ReferrerEarnings),
+            supported_chains: LookupMap::new(StorageKey::SupportedChains),
+            supported_chain_ids: Vec::new(),
+            legacy_user_profits: LookupMap::new(StorageKey::LegacyUserProfits),
+            max_price_age_ns: U64(0),
+            all_execution_ids: Vector::new(StorageKey::AllExecutionIds),
+            allowed_pairs: Vec::new(),
+            first_execution_bonus: U128(0),
+            pairs_seen: LookupMap::new(StorageKey::PairsSeen),
+            allowed_curve_types: vec![CURVE_TYPE_ED25519],
+            user_defaults: LookupMap::new(StorageKey::UserDefaults),
+            paused: false,
+            dex_contract: None,
+            oracle_accounts: Vec::new(),
+            max_consecutive_failures: 0,
+            max_executions_per_day: 0,
+            daily_executions: LookupMap::new(StorageKey::DailyExecutions),
+            oracle_quorum: 1,
+            user_earned_tokens: LookupMap::new(StorageKey::UserEarnedTokens),
+            cancellation_fee_bps: 0,
+            max_active_intents_per_user: 0,
+            pair_stats: LookupMap::new(StorageKey::PairStats),
+            profit_epochs: LookupMap::new(StorageKey::ProfitEpochs),
+            profit_epoch_count: LookupMap::new(StorageKey::ProfitEpochCount),
+            pk_intents: LookupMap::new(StorageKey::PkIntents),
+            profit_factor_bps: 8_000,
+            templates: LookupMap::new(StorageKey::Templates),
+            circuit_breaker_threshold: U128(0),
+            outflow_window_start: U64(0),
+            outflow_window_amount: U128(0),
+            default_keeper_fee_bps: 0,
+            cancelled_intents: LookupMap::new(StorageKey::CancelledIntents),
+            pair_min_threshold: LookupMap::new(StorageKey::PairMinThreshold),
+            roles: LookupMap::new(StorageKey::Roles),
+            blocklist: LookupMap::new(StorageKey::Blocklist),
+            storage_deposit: U128(NearToken::from_near(1).as_yoctonear()),
+            creation_fee: U128(0),
+            reserve_ratio_bps: 0,
+            attention_window_ns: U64(NANOS_PER_DAY),
+            intent_deposits: U128(0),
+            token_creation_fees: LookupMap::new(StorageKey::TokenCreationFees),
+            allowed_token_ids: Vec::new(),
+            settled_executions: 0,
+            merged_intents: LookupMap::new(StorageKey::MergedIntents),
+            migration_cursor: None,
+        }
+    }
+
+    /// Rewrites state stored in the `OldArbitrageContract` layout into the
+    /// current one. Upgrade order: deploy the new wasm to the contract account,
+    /// then call `migrate` from that same account before any other method,
+    /// since every other call would fail to deserialize the old state.
+    /// Collections keep their storage prefixes. Intent and execution records
+    /// are left in their v1 layout for `migrate_records` to rewrite a page
+    /// at a time, and the contract stays paused until it has.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: OldArbitrageContract = env::state_read().expect("Failed to read old state");
+        let migration_cursor = MigrationCursor {
+            next_intent_id: 1,
+            intent_end: old.next_intent_id,
+            next_execution_id: 1,
+            execution_end: old.next_execution_id,
+        };
+        Self {
+            owner: old.owner.clone(),
+            intents: LookupMap::new(b"intents".to_vec()),
+            user_intents: old.user_intents,
+            executions: LookupMap::new(b"executions".to_vec()),
+            user_executions: old.user_executions,
+            user_profits: LookupMap::new(StorageKey::UserProfits),
+            next_intent_id: old.next_intent_id,
+            next_execution_id: old.next_execution_id,
+            cross_chain_signatures: old.cross_chain_signatures,
+            total_deposited: LookupMap::new(StorageKey::TotalDeposited),
+            executed_keys: LookupMap::new(StorageKey::ExecutedKeys),
+            max_profit_threshold: DEFAULT_MAX_PROFIT_THRESHOLD,
+            intent_executions: LookupMap::new(StorageKey::IntentExecutions),
+            state_version: STATE_VERSION,
+            claimable_profits: LookupMap::new(StorageKey::ClaimableProfits),
+            user_profit_tokens: LookupMap::new(StorageKey::UserProfitTokens),
+            total_profit_paid: U128(0),
+            total_volume: U128(0),
+            user_execution_nonces: LookupMap::new(StorageKey::UserExecutionNonces),
+            reserved_profits: U128(0),
+            execution_cooldown_ns: U64(0),
+            all_intent_ids: Vector::new(StorageKey::AllIntentIds),
+            fee_basis_points: 0,
+            treasury: old.owner,
+            treasury_earnings: U128(0),
+            referrer_bps: 0,
+            referrer_earnings: LookupMap::new(StorageKey::ReferrerEarnings),
+            supported_chains: LookupMap::new(StorageKey::SupportedChains),
+            supported_chain_ids: Vec::new(),
+            legacy_user_profits: old.user_profits,
+            max_price_age_ns: U64(0),
+            all_execution_ids: Vector::new(StorageKey::AllExecutionIds),
+            allowed_pairs: Vec::new(),
+            first_execution_bonus: U128(0),
+            pairs_seen: LookupMap::new(StorageKey::PairsSeen),
+            allowed_curve_types: vec![CURVE_TYPE_ED25519],
+            user_defaults: LookupMap::new(StorageKey::UserDefaults),
+            paused: true,
+            dex_contract: None,
+            oracle_accounts: Vec::new(),
+            max_consecutive_failures: 0,
+            max_executions_per_day: 0,
+            daily_executions: LookupMap::new(StorageKey::DailyExecutions),
+            oracle_quorum: 1,
+            user_earned_tokens: LookupMap::new(StorageKey::UserEarnedTokens),
+            cancellation_fee_bps: 0,
+            max_active_intents_per_user: 0,
+            pair_stats: LookupMap::new(StorageKey::PairStats),
+            profit_epochs: LookupMap::new(StorageKey::ProfitEpochs),
+            profit_epoch_count: LookupMap::new(StorageKey::ProfitEpochCount),
+            pk_intents: LookupMap::new(StorageKey::PkIntents),
+            profit_factor_bps: 8_000,
+            templates: LookupMap::new(StorageKey::Templates),
+            circuit_breaker_threshold: U128(0),
+            outflow_window_start: U64(0),
+            outflow_window_amount: U128(0),
+            default_keeper_fee_bps: 0,
+            cancelled_intents: LookupMap::new(StorageKey::CancelledIntents),
+            pair_min_threshold: LookupMap::new(StorageKey::PairMinThreshold),
+            roles: LookupMap::new(StorageKey::Roles),
+            blocklist: LookupMap::new(StorageKey::Blocklist),
+            storage_deposit: U128(NearToken::from_near(1).as_yoctonear()),
+            creation_fee: U128(0),
+            reserve_ratio_bps: 0,
+            attention_window_ns: U64(NANOS_PER_DAY),
+            intent_deposits: U128(0),
+            token_creation_fees: LookupMap::new(StorageKey::TokenCreationFees),
+            allowed_token_ids: Vec::new(),
+            settled_executions: 0,
+            merged_intents: LookupMap::new(StorageKey::MergedIntents),
+            migration_cursor: Some(migration_cursor),
+        }
+    }
+
+    /// Rewrites up to `limit` (at most 100) of the v1 records `migrate`
+    /// left, intents before executions, and rebuilds what is derived from
+    /// them: the global id lists, `intent_executions`, `total_deposited`,
+    /// `total_volume`, `intent_deposits` and the settled execution count.
+    /// Returns whether records remain; call it again until it returns false,
+    /// which also unpauses the contract.
+    pub fn migrate_records(&mut self, limit: u64) -> bool {
+        self.assert_role(Role::Admin);
+        let mut cursor = self.migration_cursor.take().expect("No migration in progress");
+        let mut budget = limit.min(100);
+
+        let old_intents: LookupMap<String, OldArbitrageIntent> =
+            LookupMap::new(b"intents".to_vec());
+        while budget > 0 && cursor.next_intent_id < cursor.intent_end {
+            let intent_id = cursor.next_intent_id.to_string();
+            if let Some(old) = old_intents.get(&intent_id) {
+                let intent = migrate_intent(old);
+                self.intent_deposits = U128(
+                    self.intent_deposits.0 + intent.deposit.0 - intent.executed_amount.0,
+                );
+                let deposited = self.total_deposited.get(&intent.user).unwrap_or(U128(0));
+                self.total_deposited.insert(&intent.user, &U128(deposited.0 + intent.deposit.0));
+                self.intents.insert(&intent_id, &intent);
+                self.all_intent_ids.push(&intent_id);
+            }
+            cursor.next_intent_id += 1;
+            budget -= 1;
+        }
+
+        let old_executions: LookupMap<String, OldArbitrageExecution> =
+            LookupMap::new(b"executions".to_vec());
+        while budget > 0 && cursor.next_execution_id < cursor.execution_end {
+            let execution_id = cursor.next_execution_id.to_string();
+            if let Some(old) = old_executions.get(&execution_id) {
+                let execution = migrate_execution(old);
+                let mut intent_execution_list =
+                    self.intent_executions.get(&execution.intent_id).unwrap_or_else(|| {
+                        Vector::new(StorageKey::IntentExecutionList {
+                            intent_hash: env::sha256_array(execution.intent_id.as_bytes()),
+                        })
+                    });
+                intent_execution_list.push(&execution_id);
+                self.intent_executions.insert(&execution.intent_id, &intent_execution_list);
+                self.total_volume = U128(self.total_volume.0 + execution.amount.0);
+                // v1 only recorded executions it had completed
+                self.settled_executions += 1;
+                self.executions.insert(&execution_id, &execution);
+                self.all_execution_ids.push(&execution_id);
+            }
+            cursor.next_execution_id += 1;
+            budget -= 1;
+        }
+
+        if cursor.next_intent_id < cursor.intent_end
+            || cursor.next_execution_id < cursor.execution_end
+        {
+            log!(
+                "Migrated up to intent {} and execution {}",
+                cursor.next_intent_id,
+                cursor.next_execution_id
+            );
+            self.migration_cursor = Some(cursor);
+            return true;
         }
+        self.paused = false;
+        log!("Migrated all v1 records");
+        false
     }
 
     // Intent Management
     #[payable]
+    #[allow(clippy::too_many_arguments)]
     pub fn create_intent(
         &mut self,
         token_pair: String,
-        min_profit_threshold: String,
+        min_profit_threshold: Option<String>,
+        label: Option<String>,
+        min_absolute_profit: Option<U128>,
+        referrer: Option<AccountId>,
+        activate_at: Option<U64>,
+        expires_at: Option<U64>,
+        dex: Option<AccountId>,
+        min_price_diff: Option<String>,
+        condition: Option<ExternalCondition>,
+        target_price: Option<String>,
+        direction: Option<PriceDirection>,
     ) -> String {
         let user = env::predecessor_account_id();
-        let deposit = env::attached_deposit();
+        let deposit = self.take_deposit(&user);
 
-        // Compare deposit (u128 in yoctoNEAR) with 1 NEAR in yoctoNEAR
+        let params = IntentParams {
+            token_pair,
+            min_profit_threshold,
+            label,
+            min_absolute_profit,
+            referrer,
+            activate_at,
+            expires_at,
+            dex,
+            min_price_diff,
+            max_executions: None,
+            condition,
+            target_price,
+            direction,
+        };
+        self.internal_create_intent(user, params, deposit, None)
+    }
+
+    /// Creates an intent for the caller from a template registered by the
+    /// owner. Takes the same deposit as `create_intent`.
+    #[payable]
+    pub fn create_intent_from_template(&mut self, name: String) -> String {
+        let template = self.templates.get(&name).expect("Template not found");
+        let user = env::predecessor_account_id();
+        let deposit = self.take_deposit(&user);
+
+        let params = IntentParams {
+            token_pair: template.token_pair,
+            min_profit_threshold: Some(template.min_profit_threshold),
+            label: None,
+            min_absolute_profit: None,
+            referrer: None,
+            activate_at: None,
+            expires_at: None,
+            dex: None,
+            min_price_diff: None,
+            max_executions: template.max_executions,
+            condition: None,
+            target_price: None,
+            direction: None,
+        };
+        self.internal_create_intent(user, params, deposit, None)
+    }
+
+    /// Checks the attached NEAR of a new intent, moves the creation fee to the
+    /// treasury and adds the rest to the user's lifetime deposits. Returns the
+    /// intent's deposit in yoctoNEAR.
+    fn take_deposit(&mut self, user: &AccountId) -> u128 {
+        let attached = env::attached_deposit().as_yoctonear();
+        let required = self.storage_deposit.0 + self.creation_fee.0;
         assert!(
-            deposit >= 1_000_000_000_000_000_000_000_000, // 1 NEAR = 10^24 yoctoNEAR
-            "Minimum 1 NEAR deposit required"
+            attached >= required,
+            "Minimum deposit of {} yoctoNEAR required, including a creation fee of {}",
+            required,
+            self.creation_fee.0
         );
 
-        let intent_id = self.next_intent_id.to_string();
-        self.next_intent_id += 1;
+        self.treasury_earnings = U128(self.treasury_earnings.0 + self.creation_fee.0);
+        let deposit = attached - self.creation_fee.0;
+        let deposited = self.total_deposited.get(user).unwrap_or(U128(0));
+        self.total_deposited.insert(user, &U128(deposited.0 + deposit));
+        deposit
+    }
 
-        let min_threshold: f64 = min_profit_threshold.parse().unwrap_or_else(|_| {
-            env::panic_str("Invalid min_profit_threshold: must be a valid number")
+    /// NEP-141 receiver: funds a new intent with the transferred tokens of a
+    /// whitelisted token. `msg` must be a JSON-encoded `IntentParams`. The
+    /// token's creation fee goes to the treasury and the rest becomes the
+    /// intent's deposit, so nothing is returned to the sender.
+    ///
+    /// A token transfer can't carry NEAR, so these intents are exempt from
+    /// `storage_deposit`: the contract's own balance pays for their storage,
+    /// bounded by the token whitelist, its creation fee and the per-user
+    /// active intent limit. Their deposit is in token units, so it isn't
+    /// added to the NEAR-denominated `total_deposited` or `intent_deposits`.
+    pub fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> U128 {
+        let token_id = env::predecessor_account_id();
+        // Anyone can call this claiming to be a token, so only listed
+        // contracts may create intents on a sender's behalf
+        let creation_fee = self
+            .token_creation_fees
+            .get(&token_id)
+            .unwrap_or_else(|| env::panic_str(&format!("Token {} is not whitelisted", token_id)));
+        assert!(amount.0 > 0, "Transfer amount must be positive");
+        assert!(
+            amount.0 > creation_fee.0,
+            "Transfer amount must exceed the creation fee of {}",
+            creation_fee.0
+        );
+
+        let params: IntentParams = serde_json::from_str(&msg).unwrap_or_else(|_| {
+            env::panic_str("Invalid msg: expected JSON-encoded IntentParams")
         });
 
+        let deposit = amount.0 - creation_fee.0;
+        self.internal_create_intent(sender_id, params, deposit, Some(token_id.clone()));
+        if creation_fee.0 > 0 {
+            // Token fees can't sit in the NEAR-denominated treasury balance
+            let _ = pay_out(&token_id, self.treasury.clone(), creation_fee.0);
+        }
+        U128(0)
+    }
+
+    fn internal_create_intent(
+        &mut self,
+        user: AccountId,
+        params: IntentParams,
+        deposit: u128,
+        funding_token: Option<AccountId>,
+    ) -> String {
+        assert!(!self.paused, "Contract is paused");
+        self.assert_not_blocked(&user);
+        self.assert_pair_allowed(&params.token_pair);
+        assert_valid_label(&params.label);
+        if let Some(condition) = &params.condition {
+            assert!(!condition.method.is_empty(), "Condition method must not be empty");
+        }
+        assert!(params.referrer.as_ref() != Some(&user), "Cannot refer yourself");
+        if let Some(expires_at) = params.expires_at {
+            assert!(expires_at.0 > env::block_timestamp(), "expires_at must be in the future");
+        }
+        assert!(
+            self.max_active_intents_per_user == 0
+                || self.open_intent_count(&user) < self.max_active_intents_per_user,
+            "Active intent limit of {} reached",
+            self.max_active_intents_per_user
+        );
+        let intent_id = self.next_intent_id.to_string();
+        // A counter behind the stored ids (e.g. after a bad migration) must not
+        // silently overwrite an existing intent
+        assert!(self.intents.get(&intent_id).is_none(), "Intent {} already exists", intent_id);
+        self.next_intent_id = self
+            .next_intent_id
+            .checked_add(1)
+            .unwrap_or_else(|| env::panic_str("id counter overflow"));
+
+        let min_threshold = params
+            .min_profit_threshold
+            .or_else(|| self.user_defaults.get(&user))
+            .map(|threshold| parse_threshold(&threshold))
+            .unwrap_or_else(|| {
+                env::panic_str("No min_profit_threshold given and no default threshold set")
+            });
+        assert!(
+            min_threshold <= self.max_profit_threshold,
+            "min_profit_threshold exceeds maximum of {}",
+            self.max_profit_threshold
+        );
+        if let Some(pair_min) = self.pair_min_threshold.get(&normalize_pair(&params.token_pair)) {
+            assert!(
+                min_threshold >= parse_threshold(&pair_min),
+                "min_profit_threshold below pair minimum of {}",
+                pair_min
+            );
+        }
+        let min_price_diff = params.min_price_diff.as_deref().map(parse_min_price_diff);
+        let kind = match (params.target_price, params.direction) {
+            (None, None) => IntentKind::Spread,
+            (Some(target_price), Some(direction)) => {
+                let target_price: f64 = target_price.parse().unwrap_or_else(|_| {
+                    env::panic_str("Invalid target_price: must be a valid number")
+                });
+                assert!(
+                    target_price.is_finite() && target_price > 0.0,
+                    "target_price must be a finite positive number"
+                );
+                IntentKind::LimitOrder { target_price, direction }
+            }
+            _ => env::panic_str("target_price and direction must be given together"),
+        };
+
         let intent = ArbitrageIntent {
             id: intent_id.clone(),
             user: user.clone(),
-            token_pair,
+            token_pair: normalize_pair(&params.token_pair),
             min_profit_threshold: min_threshold,
             status: IntentStatus::Active,
             created_at: U64(env::block_timestamp()),
+            funding_token,
+            deposit: U128(deposit),
+            executed_amount: U128(0),
+            admin_locked: false,
+            authorized_keepers: Vec::new(),
+            label: params.label,
+            last_executed_at: U64(0),
+            min_absolute_profit: params.min_absolute_profit,
+            referrer: params.referrer,
+            activate_at: params.activate_at,
+            expires_at: params.expires_at,
+            keeper_fee_bps: self.default_keeper_fee_bps,
+            consecutive_failures: 0,
+            dex: params.dex,
+            min_price_diff,
+            signer_pk: Some(env::signer_account_pk()),
+            max_executions: params.max_executions,
+            execution_count: 0,
+            condition: params.condition,
+            kind,
+            paused_at: None,
+            interval_ns: None,
+            owner_only_execution: true,
+            strict_threshold: false,
+            pending_swaps: 0,
         };
 
         self.intents.insert(&intent_id, &intent);
+        self.all_intent_ids.push(&intent_id);
+        if intent.funding_token.is_none() {
+            self.intent_deposits = U128(self.intent_deposits.0 + deposit);
+        }
 
-        let mut user_intent_list = self.user_intents.get(&user).unwrap_or_else(|| {
-            Vector::new(format!("user_intents_{}", &user).as_bytes())
+        self.add_user_intent(&user, &intent_id);
+        let signer_pk = env::signer_account_pk();
+        let mut pk_intent_list = self.pk_intents.get(&signer_pk).unwrap_or_else(|| {
+            Vector::new(StorageKey::PkIntentList {
+                key_hash: env::sha256_array(String::from(&signer_pk).as_bytes()),
+            })
         });
-        user_intent_list.push(&intent_id);
-        self.user_intents.insert(&user, &user_intent_list);
+        pk_intent_list.push(&intent_id);
+        self.pk_intents.insert(&signer_pk, &pk_intent_list);
 
         log!("Created intent {} for user {}", intent_id, user);
         intent_id
@@ -136,242 +1403,7462 @@ impl ArbitrageContract {
         let mut intent = self.intents.get(&intent_id).expect("Intent not found");
 
         assert_eq!(intent.user, user, "Only intent owner can pause");
+        assert!(
+            !matches!(intent.status, IntentStatus::Executed),
+            "Cannot pause an executed intent"
+        );
         intent.status = IntentStatus::Paused;
+        intent.paused_at = Some(U64(env::block_timestamp()));
         self.intents.insert(&intent_id, &intent);
         log!("Paused intent {}", intent_id);
     }
 
+    /// Pauses every active intent of the caller and returns how many were
+    /// paused. Paused and executed intents are left as they are.
+    pub fn pause_all_intents(&mut self) -> u64 {
+        let user = env::predecessor_account_id();
+        let Some(intent_ids) = self.user_intents.get(&user) else {
+            return 0;
+        };
+
+        let mut paused = 0;
+        for intent_id in intent_ids.iter() {
+            let Some(mut intent) = self.intents.get(&intent_id) else {
+                continue;
+            };
+            if matches!(intent.status, IntentStatus::Active) {
+                intent.status = IntentStatus::Paused;
+                intent.paused_at = Some(U64(env::block_timestamp()));
+                self.intents.insert(&intent_id, &intent);
+                paused += 1;
+            }
+        }
+        log!("Paused {} intents of {}", paused, user);
+        paused
+    }
+
     pub fn resume_intent(&mut self, intent_id: String) {
         let user = env::predecessor_account_id();
         let mut intent = self.intents.get(&intent_id).expect("Intent not found");
 
         assert_eq!(intent.user, user, "Only intent owner can resume");
+        assert!(!intent.admin_locked, "Intent is locked by the contract owner");
+        assert!(!intent.is_expired(), "Cannot resume expired intent");
+        assert!(
+            !matches!(intent.status, IntentStatus::Executed),
+            "Cannot resume an executed intent"
+        );
         intent.status = IntentStatus::Active;
+        intent.paused_at = None;
         self.intents.insert(&intent_id, &intent);
         log!("Resumed intent {}", intent_id);
     }
 
-    // Arbitrage Execution
-    #[payable]
-    pub fn execute_arbitrage(
-        &mut self,
-        intent_id: String,
-        near_price: String,
-        eth_price: String,
-    ) -> Promise {
+    pub fn set_intent_label(&mut self, intent_id: String, label: Option<String>) {
         let user = env::predecessor_account_id();
-        let intent = self.intents.get(&intent_id).expect("Intent not found");
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
+
+        assert_eq!(intent.user, user, "Only intent owner can set label");
+        assert_valid_label(&label);
+        intent.label = label;
+        self.intents.insert(&intent_id, &intent);
+        log!("Updated label of intent {}", intent_id);
+    }
+
+    /// Makes the intent execute at most once per `interval_ns`, measured from
+    /// its latest execution. `None` removes the interval.
+    pub fn set_intent_interval(&mut self, intent_id: String, interval_ns: Option<U64>) {
+        let user = env::predecessor_account_id();
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
+
+        assert_eq!(intent.user, user, "Only intent owner can set interval");
+        intent.interval_ns = interval_ns;
+        self.intents.insert(&intent_id, &intent);
+        log!("Updated interval of intent {}", intent_id);
+    }
 
-        assert_eq!(intent.user, user, "Only intent owner can execute");
+    /// Threshold applied to the caller's future intents created without one.
+    pub fn set_default_threshold(&mut self, threshold: String) {
+        let user = env::predecessor_account_id();
+        parse_threshold(&threshold);
+        self.user_defaults.insert(&user, &threshold);
+        log!("Set default threshold of {} to {}", user, threshold);
+    }
+
+    /// Repoints a not yet fully executed intent at another pair, keeping its
+    /// id, deposit and execution history.
+    pub fn update_intent_pair(&mut self, intent_id: String, new_pair: String) {
+        let user = env::predecessor_account_id();
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
+
+        assert_eq!(intent.user, user, "Only intent owner can update pair");
         assert!(
-            matches!(intent.status, IntentStatus::Active),
-            "Intent must be active"
+            !matches!(intent.status, IntentStatus::Executed),
+            "Cannot update pair of an executed intent"
         );
+        self.assert_pair_allowed(&new_pair);
+        let old_pair = std::mem::replace(&mut intent.token_pair, normalize_pair(&new_pair));
+        self.intents.insert(&intent_id, &intent);
+        log!("Moved intent {} from {} to {}", intent_id, old_pair, intent.token_pair);
+    }
 
-        let near_price_f64: f64 = near_price.parse().unwrap_or_else(|_| {
-            env::panic_str("Invalid near_price: must be a valid number")
-        });
-        let eth_price_f64: f64 = eth_price.parse().unwrap_or_else(|_| {
-            env::panic_str("Invalid eth_price: must be a valid number")
-        });
+    /// Folds `merge_id` into `keep_id`: deposits, executed notional and
+    /// execution counts are summed, `merge_id`'s executions are re-pointed at
+    /// `keep_id`, and `merge_id` is deleted and recorded as merged. Both must
+    /// be open with no swap in flight and execute on the same kind and
+    /// condition. Everything else is `keep_id`'s, threshold, expiry and status
+    /// included, except `max_executions`: the caps add up, so each intent's
+    /// remaining executions carry over, and no cap on either means none.
+    pub fn merge_intents(&mut self, keep_id: String, merge_id: String) {
+        let user = env::predecessor_account_id();
+        assert_ne!(keep_id, merge_id, "Cannot merge an intent with itself");
+        let mut keep = self.intents.get(&keep_id).expect("Intent not found");
+        let merge = self.intents.get(&merge_id).expect("Intent not found");
+
+        assert_eq!(keep.user, user, "Only intent owner can merge intents");
+        assert_eq!(merge.user, user, "Only intent owner can merge intents");
+        assert_eq!(keep.token_pair, merge.token_pair, "Cannot merge intents on different pairs");
+        assert_eq!(
+            keep.funding_token, merge.funding_token,
+            "Cannot merge intents funded with different tokens"
+        );
+        assert!(!keep.admin_locked && !merge.admin_locked, "Intent is locked by admin");
+        assert!(
+            !matches!(keep.status, IntentStatus::Executed)
+                && !matches!(merge.status, IntentStatus::Executed),
+            "Cannot merge an executed intent"
+        );
+        assert!(
+            keep.pending_swaps == 0 && merge.pending_swaps == 0,
+            "Intent has a swap in flight"
+        );
+        assert!(keep.kind == merge.kind, "Cannot merge intents of different kinds");
+        assert!(
+            keep.condition == merge.condition,
+            "Cannot merge intents with different conditions"
+        );
+
+        if let Some(mut merged_executions) = self.intent_executions.get(&merge_id) {
+            let mut keep_executions = self
+                .intent_executions
+                .get(&keep_id)
+                .unwrap_or_else(|| {
+                    Vector::new(StorageKey::IntentExecutionList {
+                        intent_hash: env::sha256_array(keep_id.as_bytes()),
+                    })
+                });
+            for execution_id in merged_executions.iter() {
+                if let Some(mut execution) = self.executions.get(&execution_id) {
+                    execution.intent_id = keep_id.clone();
+                    self.executions.insert(&execution_id, &execution);
+                }
+                keep_executions.push(&execution_id);
+            }
+            merged_executions.clear();
+            self.intent_executions.remove(&merge_id);
+            self.intent_executions.insert(&keep_id, &keep_executions);
+        }
+
+        keep.deposit = U128(keep.deposit.0 + merge.deposit.0);
+        keep.executed_amount = U128(keep.executed_amount.0 + merge.executed_amount.0);
+        keep.execution_count += merge.execution_count;
+        keep.max_executions = keep.max_executions.zip(merge.max_executions).map(|(a, b)| a + b);
+        keep.last_executed_at = U64(keep.last_executed_at.0.max(merge.last_executed_at.0));
+        self.intents.insert(&keep_id, &keep);
+        self.intents.remove(&merge_id);
+        self.merged_intents.insert(&merge_id, &keep_id);
 
-        let price_diff = (near_price_f64 - eth_price_f64).abs();
-        let profit_percentage = (price_diff / near_price_f64.min(eth_price_f64)) * 100.0;
+        self.remove_user_intent(&user, &merge_id);
+        if let Some(signer_pk) = &merge.signer_pk {
+            self.remove_pk_intent(signer_pk, &merge_id);
+        }
+
+        log!("Merged intent {} into {}", merge_id, keep_id);
+    }
+
+    /// Hands an intent to another account, e.g. after the owner moves to a
+    /// new NEAR account. Its executions stay linked through the intent id,
+    /// while its deposit counts towards the new owner's `total_deposited`.
+    /// The new owner's key is unknown, so the intent leaves `pk_intents`.
+    pub fn transfer_intent(&mut self, intent_id: String, new_owner: AccountId) {
+        assert!(!self.paused, "Contract is paused");
+        let user = env::predecessor_account_id();
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
 
+        assert_eq!(intent.user, user, "Only intent owner can transfer");
+        assert_ne!(new_owner, user, "Intent already belongs to this account");
+        self.assert_not_blocked(&user);
+        self.assert_not_blocked(&new_owner);
+        // Settlement pays whoever owns the intent when the swap resolves
+        assert_eq!(intent.pending_swaps, 0, "Intent has a swap in flight");
         assert!(
-            profit_percentage >= intent.min_profit_threshold,
-            "Profit below threshold"
+            matches!(intent.status, IntentStatus::Executed)
+                || self.max_active_intents_per_user == 0
+                || self.open_intent_count(&new_owner) < self.max_active_intents_per_user,
+            "Active intent limit of {} reached",
+            self.max_active_intents_per_user
         );
+        intent.user = new_owner.clone();
+        intent.authorized_keepers.retain(|keeper| *keeper != new_owner);
+        if intent.referrer.as_ref() == Some(&new_owner) {
+            intent.referrer = None;
+        }
+        if let Some(signer_pk) = intent.signer_pk.take() {
+            self.remove_pk_intent(&signer_pk, &intent_id);
+        }
+        self.intents.insert(&intent_id, &intent);
 
-        self.execute_near_dex_swap(intent_id, near_price_f64, eth_price_f64)
+        if intent.funding_token.is_none() {
+            let deposited = self.total_deposited.get(&user).unwrap_or(U128(0));
+            self.total_deposited.insert(&user, &U128(deposited.0.saturating_sub(intent.deposit.0)));
+            let deposited = self.total_deposited.get(&new_owner).unwrap_or(U128(0));
+            self.total_deposited.insert(&new_owner, &U128(deposited.0 + intent.deposit.0));
+        }
+        self.remove_user_intent(&user, &intent_id);
+        self.add_user_intent(&new_owner, &intent_id);
+        log!("Transferred intent {} from {} to {}", intent_id, user, new_owner);
     }
 
-    fn execute_near_dex_swap(
+    /// Deletes an intent and refunds its unexecuted deposit, in the token it
+    /// was funded with, minus `cancellation_fee_bps` for the treasury.
+    /// Resolves to the amount refunded, 0 when there is nothing to pay out
+    /// or the refund failed and is left claimable instead.
+    pub fn cancel_intent(&mut self, intent_id: String) -> PromiseOrValue<U128> {
+        let user = env::predecessor_account_id();
+        let intent = self.intents.get(&intent_id).expect("Intent not found");
+
+        assert_eq!(intent.user, user, "Only intent owner can cancel");
+        assert!(!intent.admin_locked, "Intent is locked by the contract owner");
+        // Settling needs the intent, and a failed swap gives its notional back
+        assert_eq!(intent.pending_swaps, 0, "Intent has a swap in flight");
+        self.intents.remove(&intent_id);
+        self.remove_user_intent(&user, &intent_id);
+        self.cancelled_intents.insert(&intent_id, &U64(env::block_timestamp()));
+
+        let remaining = intent.deposit.0 - intent.executed_amount.0;
+        if intent.funding_token.is_none() {
+            self.intent_deposits = U128(self.intent_deposits.0 - remaining);
+        }
+        let fee = remaining * self.cancellation_fee_bps as u128 / 10_000;
+        let refund = remaining - fee;
+        let token_id = intent.funding_token.unwrap_or_else(env::current_account_id);
+        log!("Cancelled intent {}, refunding {} with fee {}", intent_id, refund, fee);
+
+        // Token fees can't sit in the NEAR-denominated treasury balance, so
+        // they are sent on their own and don't hold up the refund
+        if fee > 0 {
+            if token_id == env::current_account_id() {
+                self.treasury_earnings = U128(self.treasury_earnings.0 + fee);
+            } else {
+                let _ = self.pay_out_resolved(&token_id, self.treasury.clone(), fee);
+            }
+        }
+        if refund == 0 {
+            return PromiseOrValue::Value(U128(0));
+        }
+        PromiseOrValue::Promise(self.pay_out_resolved(&token_id, user, refund))
+    }
+
+    /// `pay_out` followed by `on_refund_resolved`, so that a payment that
+    /// fails is kept for the receiver as claimable instead of being lost.
+    fn pay_out_resolved(&self, token_id: &AccountId, receiver: AccountId, amount: u128) -> Promise {
+        pay_out(token_id, receiver.clone(), amount).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_WITHDRAW_CALLBACK)
+                .on_refund_resolved(receiver, token_id.clone(), U128(amount)),
+        )
+    }
+
+    /// Resolves a refund: returns the amount paid, or credits it to the
+    /// receiver as claimable and returns 0 if the payment failed.
+    #[private]
+    pub fn on_refund_resolved(
         &mut self,
-        intent_id: String,
-        near_price: f64,
-        eth_price: f64,
-    ) -> Promise {
-        let execution_id = self.next_execution_id.to_string();
-        self.next_execution_id += 1;
+        receiver: AccountId,
+        token_id: AccountId,
+        amount: U128,
+    ) -> U128 {
+        if env::promise_result_checked(0, MAX_PAYOUT_RESULT_LEN).is_ok() {
+            return amount;
+        }
+        self.credit_claimable_profit(&receiver, &token_id, amount.0);
+        log!("Refund of {} of {} to {} failed, kept as claimable", amount.0, token_id, receiver);
+        U128(0)
+    }
 
-        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
+    /// Intents of `user` that are still active or paused.
+    fn open_intent_count(&self, user: &AccountId) -> u32 {
+        let Some(intent_ids) = self.user_intents.get(user) else {
+            return 0;
+        };
+        intent_ids
+            .iter()
+            .filter_map(|intent_id| self.intents.get(&intent_id))
+            .filter(|intent| !matches!(intent.status, IntentStatus::Executed))
+            .count() as u32
+    }
 
-        let price_diff = (near_price - eth_price).abs();
-        let profit = price_diff * 0.8; // 80% of price difference as profit
-        let gas_fees = 0.01; // Placeholder gas fee in NEAR
+    /// Keeps the user's list in creation order: new intents go last, while a
+    /// transferred one moves back past any created after it.
+    fn add_user_intent(&mut self, user: &AccountId, intent_id: &String) {
+        let mut user_intent_list = self.user_intents.get(user).unwrap_or_else(|| {
+            Vector::new(StorageKey::UserIntentList {
+                account_hash: env::sha256_array(user.as_bytes()),
+            })
+        });
+        let created_at = self.intents.get(intent_id).expect("Intent not found").created_at.0;
+        user_intent_list.push(intent_id);
+        let mut index = user_intent_list.len() - 1;
+        while index > 0 {
+            let previous = user_intent_list.get(index - 1).expect("Index out of bounds");
+            let created_later = self
+                .intents
+                .get(&previous)
+                .is_some_and(|intent| intent.created_at.0 > created_at);
+            if !created_later {
+                break;
+            }
+            user_intent_list.replace(index, &previous);
+            user_intent_list.replace(index - 1, intent_id);
+            index -= 1;
+        }
+        self.user_intents.insert(user, &user_intent_list);
+    }
 
-        let tx_hash = hex::encode(env::random_seed()); // Convert Vec<u8> to hex string
+    /// Removes the intent from the user's list, shifting later entries down
+    /// so the list stays in creation order.
+    fn remove_user_intent(&mut self, user: &AccountId, intent_id: &str) {
+        let mut user_intent_list = self.user_intents.get(user).expect("Intent not found");
+        if let Some(index) = user_intent_list.iter().position(|id| id == intent_id) {
+            for next in index as u64 + 1..user_intent_list.len() {
+                let id = user_intent_list.get(next).expect("Index out of bounds");
+                user_intent_list.replace(next - 1, &id);
+            }
+            user_intent_list.pop();
+        }
+        self.user_intents.insert(user, &user_intent_list);
+    }
 
-        let execution = ArbitrageExecution {
-            id: execution_id.clone(),
-            intent_id: intent_id.clone(),
-            user: intent.user.clone(),
-            token_pair: intent.token_pair.clone(),
-            price_diff,
-            profit,
-            gas_fees,
-            tx_hash,
-            timestamp: U64(env::block_timestamp()),
-            near_price,
-            eth_price,
+    /// Removes the intent from its signer key's list, keeping the rest in
+    /// creation order like `remove_user_intent`.
+    fn remove_pk_intent(&mut self, pk: &PublicKey, intent_id: &str) {
+        let Some(mut pk_intent_list) = self.pk_intents.get(pk) else {
+            return;
         };
+        if let Some(index) = pk_intent_list.iter().position(|id| id == intent_id) {
+            for next in index as u64 + 1..pk_intent_list.len() {
+                let id = pk_intent_list.get(next).expect("Index out of bounds");
+                pk_intent_list.replace(next - 1, &id);
+            }
+            pk_intent_list.pop();
+        }
+        self.pk_intents.insert(pk, &pk_intent_list);
+    }
 
-        self.executions.insert(&execution_id, &execution);
+    /// Lets `keeper` trigger `execute_arbitrage` for this intent. Profit still
+    /// accrues to the intent owner.
+    pub fn add_keeper(&mut self, intent_id: String, keeper: AccountId) {
+        let user = env::predecessor_account_id();
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
 
-        let mut user_execution_list = self.user_executions.get(&intent.user).unwrap_or_else(|| {
-            Vector::new(format!("user_executions_{}", &intent.user).as_bytes())
-        });
-        user_execution_list.push(&execution_id);
-        self.user_executions.insert(&intent.user, &user_execution_list);
+        assert_eq!(intent.user, user, "Only intent owner can add keepers");
+        if !intent.authorized_keepers.contains(&keeper) {
+            intent.authorized_keepers.push(keeper.clone());
+            self.intents.insert(&intent_id, &intent);
+        }
+        log!("Authorized keeper {} for intent {}", keeper, intent_id);
+    }
 
-        let current_profit = self.user_profits.get(&intent.user).unwrap_or(U128(0));
-        let profit_amount = U128((profit * 1_000_000_000_000_000_000_000_000.0) as u128); // Convert to yoctoNEAR
-        self.user_profits.insert(&intent.user, &U128(current_profit.0 + profit_amount.0));
+    /// Set to execute only on profit strictly above the threshold, for
+    /// thresholds placed exactly at break-even.
+    pub fn set_strict_threshold(&mut self, intent_id: String, strict_threshold: bool) {
+        let user = env::predecessor_account_id();
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
 
-        intent.status = IntentStatus::Executed; // Update intent status
+        assert_eq!(intent.user, user, "Only intent owner can set strict threshold");
+        intent.strict_threshold = strict_threshold;
         self.intents.insert(&intent_id, &intent);
+        log!("Set strict threshold of intent {} to {}", intent_id, strict_threshold);
+    }
 
-        log!("Executed arbitrage {} with profit {}", execution_id, profit);
+    /// Set (the default) to reject every executor but the intent owner, even
+    /// authorized keepers; clear to let those keepers execute.
+    pub fn set_owner_only_execution(&mut self, intent_id: String, owner_only_execution: bool) {
+        let user = env::predecessor_account_id();
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
 
-        Promise::new(env::current_account_id())
+        assert_eq!(intent.user, user, "Only intent owner can change executors");
+        intent.owner_only_execution = owner_only_execution;
+        self.intents.insert(&intent_id, &intent);
+        log!("Set owner-only execution of intent {} to {}", intent_id, owner_only_execution);
     }
 
-    // Cross-Chain Signature Management
-    pub fn store_cross_chain_signature(
+    pub fn remove_keeper(&mut self, intent_id: String, keeper: AccountId) {
+        let user = env::predecessor_account_id();
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
+
+        assert_eq!(intent.user, user, "Only intent owner can remove keepers");
+        intent.authorized_keepers.retain(|account| *account != keeper);
+        self.intents.insert(&intent_id, &intent);
+        log!("Removed keeper {} from intent {}", keeper, intent_id);
+    }
+
+    pub fn set_keeper_fee_bps(&mut self, intent_id: String, keeper_fee_bps: u16) {
+        let user = env::predecessor_account_id();
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
+
+        assert_eq!(intent.user, user, "Only intent owner can set keeper fee");
+        assert!(keeper_fee_bps <= 10_000, "Keeper fee cannot exceed 100%");
+        intent.keeper_fee_bps = keeper_fee_bps;
+        self.intents.insert(&intent_id, &intent);
+        log!("Set keeper fee of intent {} to {} bps", intent_id, keeper_fee_bps);
+    }
+
+    // Owner Configuration
+    pub fn set_execution_cooldown(&mut self, cooldown_ns: U64) {
+        self.assert_role(Role::Admin);
+        self.execution_cooldown_ns = cooldown_ns;
+        log!("Set execution cooldown to {}ns", cooldown_ns.0);
+    }
+
+    pub fn set_first_execution_bonus(&mut self, first_execution_bonus: U128) {
+        self.assert_role(Role::FeeManager);
+        self.first_execution_bonus = first_execution_bonus;
+        log!("Set first execution bonus to {}", first_execution_bonus.0);
+    }
+
+    pub fn set_max_executions_per_day(&mut self, max_executions_per_day: u32) {
+        self.assert_role(Role::Admin);
+        self.max_executions_per_day = max_executions_per_day;
+        log!("Set max executions per day to {}", max_executions_per_day);
+    }
+
+    pub fn set_max_consecutive_failures(&mut self, max_consecutive_failures: u32) {
+        self.assert_role(Role::Admin);
+        self.max_consecutive_failures = max_consecutive_failures;
+        log!("Set max consecutive failures to {}", max_consecutive_failures);
+    }
+
+    pub fn set_attention_window(&mut self, attention_window_ns: U64) {
+        self.assert_role(Role::Admin);
+        self.attention_window_ns = attention_window_ns;
+        log!("Set attention window to {} ns", attention_window_ns.0);
+    }
+
+    pub fn set_max_price_age(&mut self, max_price_age_ns: U64) {
+        self.assert_role(Role::Admin);
+        self.max_price_age_ns = max_price_age_ns;
+        log!("Set max price age to {}ns", max_price_age_ns.0);
+    }
+
+    pub fn create_template(
         &mut self,
-        execution_id: String,
-        signature: Base64VecU8,
-        public_key: PublicKey,
-        chain_id: u64,
-        nonce: u64,
+        name: String,
+        token_pair: String,
+        min_profit_threshold: String,
+        max_executions: Option<u32>,
     ) {
-        let cross_chain_sig = CrossChainSignature {
-            signature,
-            public_key,
-            chain_id,
-            nonce,
+        self.assert_role(Role::Admin);
+        assert_valid_token_pair(&token_pair);
+        parse_threshold(&min_profit_threshold);
+        let template = IntentTemplate {
+            token_pair: normalize_pair(&token_pair),
+            min_profit_threshold,
+            max_executions,
         };
+        self.templates.insert(&name, &template);
+        log!("Registered template {}", name);
+    }
 
-        self.cross_chain_signatures.insert(&execution_id, &cross_chain_sig);
-        log!("Stored cross-chain signature for execution {}", execution_id);
+    pub fn add_allowed_pair(&mut self, token_pair: String) {
+        self.assert_role(Role::Admin);
+        assert_valid_token_pair(&token_pair);
+        let token_pair = normalize_pair(&token_pair);
+        if !self.allowed_pairs.contains(&token_pair) {
+            self.allowed_pairs.push(token_pair.clone());
+        }
+        log!("Allowed pair {}", token_pair);
     }
 
-    pub fn verify_cross_chain_signature(&self, execution_id: String) -> bool {
-        if let Some(_) = self.cross_chain_signatures.get(&execution_id) {
-            // Placeholder: Implement actual signature verification here
-            true
-        } else {
-            false
+    pub fn remove_allowed_pair(&mut self, token_pair: String) {
+        self.assert_role(Role::Admin);
+        let token_pair = normalize_pair(&token_pair);
+        self.allowed_pairs.retain(|pair| normalize_pair(pair) != token_pair);
+        log!("Removed allowed pair {}", token_pair);
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.assert_role(Role::Pauser);
+        self.paused = paused;
+        log!("Contract paused: {}", paused);
+    }
+
+    pub fn set_dex_contract(&mut self, dex_contract: Option<AccountId>) {
+        self.assert_role(Role::Admin);
+        log!("Set DEX contract to {:?}", dex_contract);
+        self.dex_contract = dex_contract;
+    }
+
+    pub fn set_oracle_accounts(&mut self, oracle_accounts: Vec<AccountId>) {
+        self.assert_role(Role::Admin);
+        log!("Set {} oracle accounts", oracle_accounts.len());
+        self.oracle_accounts = oracle_accounts;
+    }
+
+    pub fn set_oracle_quorum(&mut self, oracle_quorum: u32) {
+        self.assert_role(Role::Admin);
+        assert!(oracle_quorum > 0, "Oracle quorum must be at least 1");
+        self.oracle_quorum = oracle_quorum;
+        log!("Set oracle quorum to {}", oracle_quorum);
+    }
+
+    pub fn set_allowed_curve_types(&mut self, curve_types: Vec<u8>) {
+        self.assert_role(Role::Admin);
+        log!("Set allowed curve types to {:?}", curve_types);
+        self.allowed_curve_types = curve_types;
+    }
+
+    /// Lets intents be funded with `token_id` through `ft_transfer_call`,
+    /// charging `creation_fee` of that token per intent. Calling it again
+    /// updates the fee.
+    pub fn add_allowed_token(&mut self, token_id: AccountId, creation_fee: U128) {
+        self.assert_role(Role::Admin);
+        if self.token_creation_fees.insert(&token_id, &creation_fee).is_none() {
+            self.allowed_token_ids.push(token_id.clone());
         }
+        log!("Allowed token {} with creation fee {}", token_id, creation_fee.0);
     }
 
-    // View Methods
-    pub fn get_user_intents(&self, user: AccountId) -> Vec<ArbitrageIntent> {
-        let mut intents = Vec::new();
+    pub fn remove_allowed_token(&mut self, token_id: AccountId) {
+        self.assert_role(Role::Admin);
+        self.token_creation_fees.remove(&token_id);
+        self.allowed_token_ids.retain(|id| *id != token_id);
+        log!("Removed allowed token {}", token_id);
+    }
 
-        if let Some(user_intent_list) = self.user_intents.get(&user) {
-            for i in 0..user_intent_list.len() {
-                if let Some(intent_id) = user_intent_list.get(i) {
-                    if let Some(intent) = self.intents.get(&intent_id) {
-                        intents.push(intent);
-                    }
-                }
-            }
+    pub fn add_supported_chain(&mut self, chain_id: u64) {
+        self.assert_role(Role::Admin);
+        if self.supported_chains.insert(&chain_id, &true).is_none() {
+            self.supported_chain_ids.push(chain_id);
         }
+        log!("Added supported chain {}", chain_id);
+    }
 
-        intents
+    pub fn remove_supported_chain(&mut self, chain_id: u64) {
+        self.assert_role(Role::Admin);
+        self.supported_chains.remove(&chain_id);
+        self.supported_chain_ids.retain(|id| *id != chain_id);
+        log!("Removed supported chain {}", chain_id);
     }
 
-    pub fn get_execution_history(&self, user: AccountId) -> Vec<ArbitrageExecution> {
-        let mut executions = Vec::new();
+    pub fn set_fee_basis_points(&mut self, fee_basis_points: u16) {
+        self.assert_role(Role::FeeManager);
+        assert!(
+            fee_basis_points as u32 + self.referrer_bps as u32 <= 10_000,
+            "Fee and referrer shares cannot exceed 100%"
+        );
+        self.fee_basis_points = fee_basis_points;
+        log!("Set fee to {} bps", fee_basis_points);
+    }
 
-        if let Some(user_execution_list) = self.user_executions.get(&user) {
-            for i in 0..user_execution_list.len() {
-                if let Some(execution_id) = user_execution_list.get(i) {
-                    if let Some(execution) = self.executions.get(&execution_id) {
-                        executions.push(execution);
-                    }
-                }
-            }
-        }
+    pub fn set_referrer_bps(&mut self, referrer_bps: u16) {
+        self.assert_role(Role::FeeManager);
+        assert!(
+            self.fee_basis_points as u32 + referrer_bps as u32 <= 10_000,
+            "Fee and referrer shares cannot exceed 100%"
+        );
+        self.referrer_bps = referrer_bps;
+        log!("Set referrer share to {} bps", referrer_bps);
+    }
 
-        executions
+    pub fn set_default_keeper_fee_bps(&mut self, default_keeper_fee_bps: u16) {
+        self.assert_role(Role::FeeManager);
+        assert!(default_keeper_fee_bps <= 10_000, "Keeper fee cannot exceed 100%");
+        self.default_keeper_fee_bps = default_keeper_fee_bps;
+        log!("Set default keeper fee to {} bps", default_keeper_fee_bps);
     }
 
-    pub fn get_total_profit(&self, user: AccountId) -> U128 {
-        self.user_profits.get(&user).unwrap_or(U128(0))
+    pub fn set_max_active_intents_per_user(&mut self, max_active_intents_per_user: u32) {
+        self.assert_role(Role::Admin);
+        self.max_active_intents_per_user = max_active_intents_per_user;
+        log!("Set max active intents per user to {}", max_active_intents_per_user);
     }
 
-    pub fn get_intent(&self, intent_id: String) -> Option<ArbitrageIntent> {
-        self.intents.get(&intent_id)
+    pub fn set_profit_factor_bps(&mut self, profit_factor_bps: u16) {
+        self.assert_role(Role::FeeManager);
+        assert!(profit_factor_bps <= 10_000, "Profit factor cannot exceed 100%");
+        self.profit_factor_bps = profit_factor_bps;
+        log!("Set profit factor to {} bps", profit_factor_bps);
     }
 
-    pub fn get_execution(&self, execution_id: String) -> Option<ArbitrageExecution> {
-        self.executions.get(&execution_id)
+    pub fn set_circuit_breaker_threshold(&mut self, threshold: U128) {
+        self.assert_role(Role::Admin);
+        self.circuit_breaker_threshold = threshold;
+        log!("Set circuit breaker threshold to {}", threshold.0);
     }
 
-    pub fn get_contract_info(&self) -> serde_json::Value {
-        serde_json::json!({
-            "name": "ArbitrageAI Cross-Chain Agent",
-            "version": "1.0.0",
-            "owner": self.owner,
-            "total_intents": self.next_intent_id - 1,
-            "total_executions": self.next_execution_id - 1
-        })
+    pub fn set_reserve_ratio_bps(&mut self, reserve_ratio_bps: u16) {
+        self.assert_role(Role::Admin);
+        assert!(reserve_ratio_bps <= 10_000, "Reserve ratio cannot exceed 100%");
+        self.reserve_ratio_bps = reserve_ratio_bps;
+        log!("Set reserve ratio to {} bps", reserve_ratio_bps);
     }
-}
 
-// Cross-Chain Integration Tests
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::MockedBlockchain;
-    use near_sdk::{testing_env, NearToken};
+    pub fn set_creation_fee(&mut self, creation_fee: U128) {
+        self.assert_role(Role::FeeManager);
+        self.creation_fee = creation_fee;
+        log!("Set creation fee to {}", creation_fee.0);
+    }
 
-    fn get_context(predecessor_account_id: AccountId) -> VMContextBuilder {
-        let mut builder = VMContextBuilder::new();
-        builder
-            .current_account_id(accounts(0))
-            .signer_account_id(predecessor_account_id.clone())
-            .predecessor_account_id(predecessor_account_id)
-            .attached_deposit(NearToken::from_near(1).as_yoctonear());
-        builder
+    pub fn set_storage_deposit(&mut self, storage_deposit: U128) {
+        self.assert_role(Role::Admin);
+        assert!(storage_deposit.0 > 0, "Storage deposit must be positive");
+        self.storage_deposit = storage_deposit;
+        log!("Set storage deposit to {}", storage_deposit.0);
     }
 
-    #[test]
-    fn test_create_intent() {
-        let mut context = get_context(accounts(1));
-        testing_env!(context.build());
+    pub fn set_cancellation_fee_bps(&mut self, cancellation_fee_bps: u16) {
+        self.assert_role(Role::FeeManager);
+        assert!(cancellation_fee_bps <= 2_000, "Cancellation fee cannot exceed 20%");
+        self.cancellation_fee_bps = cancellation_fee_bps;
+        log!("Set cancellation fee to {} bps", cancellation_fee_bps);
+    }
 
-        let mut contract = ArbitrageContract::new(accounts(0));
-        let intent_id = contract.create_intent("ETH/USDC".to_string(), "1.0".to_string());
+    /// Closes the user's current accounting period: archives their realized
+    /// NEAR profit as the next epoch and zeroes the counter. Only affects
+    /// reporting; claimable balances and payouts are untouched. Returns the
+    /// closed epoch.
+    pub fn reset_profit_counter(&mut self, user: AccountId) -> u32 {
+        self.assert_role(Role::Admin);
+        let native = env::current_account_id();
+        let profit = self.get_total_profit(user.clone(), native.clone());
+        let epoch = self.profit_epoch_count.get(&user).unwrap_or(0);
 
-        assert_eq!(intent_id, "1");
-        let intent = contract.get_intent(intent_id).unwrap();
-        assert_eq!(intent.user, accounts(1));
-        assert_eq!(intent.token_pair, "ETH/USDC");
-        assert_eq!(intent.min_profit_threshold, 1.0);
+        self.profit_epochs.insert(&(user.clone(), epoch), &profit);
+        self.profit_epoch_count.insert(&user, &(epoch + 1));
+        let key = (user.clone(), native);
+        if self.user_profits.get(&key).is_some() {
+            self.user_profits.insert(&key, &U128(0));
+        }
+        self.legacy_user_profits.remove(&user);
+        log!("Closed profit epoch {} of {} at {}", epoch, user, profit.0);
+        epoch
     }
 
-    #[test]
-    fn test_execute_arbitrage() {
-        let mut context = get_context(accounts(1));
-        testing_env!(context.build());
+    /// Rebuilds `pair_stats` for `token_pair` from the execution records,
+    /// scanning at most `limit` (capped at 100) entries of the global
+    /// execution list from `from_index`. A scan from index 0 discards the
+    /// stored stats first. Returns the index to continue from, or None once
+    /// every execution has been scanned.
+    pub fn recompute_pair_stats(
+        &mut self,
+        token_pair: String,
+        from_index: u64,
+        limit: u64,
+    ) -> Option<u64> {
+        self.assert_role(Role::Admin);
+        let pair_key = normalize_pair(&token_pair);
+        let mut stats = if from_index == 0 {
+            PairStats::default()
+        } else {
+            self.pair_stats.get(&pair_key).unwrap_or_default()
+        };
 
-        let mut contract = ArbitrageContract::new(accounts(0));
-        let intent_id = contract.create_intent("ETH/USDC".to_string(), "1.0".to_string());
+        let total = self.all_execution_ids.len();
+        let to_index = from_index.saturating_add(limit.min(100)).min(total);
+        (from_index..to_index)
+            .filter_map(|index| self.all_execution_ids.get(index))
+            .filter_map(|execution_id| self.executions.get(&execution_id))
+            .filter(|execution| normalize_pair(&execution.token_pair) == pair_key)
+            .for_each(|execution| {
+                stats.record((execution.price_diff * PRICE_DIFF_SCALE).round() as i128)
+            });
+        // Empty stats would make get_pair_volatility divide by zero
+        if stats.count == 0 {
+            self.pair_stats.remove(&pair_key);
+        } else {
+            self.pair_stats.insert(&pair_key, &stats);
+        }
+        log!("Recomputed stats of {} up to execution index {}", pair_key, to_index);
 
-        context.attached_deposit(NearToken::from_near(0.1).as_yoctonear());
-        testing_env!(context.build());
+        (to_index < total).then_some(to_index)
+    }
 
-        let promise = contract.execute_arbitrage(intent_id, "3000.0".to_string(), "2950.0".to_string());
-        assert!(promise.is_valid());
+    /// Pauses any user's intent, e.g. for a flagged account. The intent owner
+    /// can still resume it unless it is also locked via `admin_set_intent_lock`.
+    pub fn admin_pause_intent(&mut self, intent_id: String) {
+        self.assert_role(Role::Pauser);
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
+        assert!(
+            !matches!(intent.status, IntentStatus::Executed),
+            "Cannot pause an executed intent"
+        );
 
+        intent.status = IntentStatus::Paused;
+        intent.paused_at = Some(U64(env::block_timestamp()));
+        self.intents.insert(&intent_id, &intent);
+        log!("Owner paused intent {}", intent_id);
+        emit_event(
+            "admin_action",
+            serde_json::json!({
+                "action": "pause_intent",
+                "intent_id": intent_id,
+                "intent_user": intent.user,
+            }),
+        );
+    }
+
+    pub fn admin_set_intent_lock(&mut self, intent_id: String, locked: bool) {
+        self.assert_role(Role::Admin);
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
+
+        intent.admin_locked = locked;
+        self.intents.insert(&intent_id, &intent);
+        emit_event(
+            "admin_action",
+            serde_json::json!({
+                "action": if locked { "lock_intent" } else { "unlock_intent" },
+                "intent_id": intent_id,
+                "intent_user": intent.user,
+            }),
+        );
+    }
+
+    pub fn set_max_profit_threshold(&mut self, max_profit_threshold: String) {
+        self.assert_role(Role::Admin);
+        let max_threshold: f64 = max_profit_threshold.parse().unwrap_or_else(|_| {
+            env::panic_str("Invalid max_profit_threshold: must be a valid number")
+        });
+        assert!(
+            max_threshold.is_finite() && max_threshold > 0.0,
+            "max_profit_threshold must be a finite positive number"
+        );
+        self.max_profit_threshold = max_threshold;
+        log!("Set max profit threshold to {}", max_threshold);
+    }
+
+    /// Sets the lowest min_profit_threshold new intents on `token_pair` may
+    /// use; `None` removes the minimum.
+    pub fn set_pair_min_threshold(&mut self, token_pair: String, min_threshold: Option<String>) {
+        self.assert_role(Role::Admin);
+        assert_valid_token_pair(&token_pair);
+        let token_pair = normalize_pair(&token_pair);
+        match min_threshold {
+            Some(min_threshold) => {
+                parse_threshold(&min_threshold);
+                log!("Set minimum threshold of {} to {}", token_pair, min_threshold);
+                self.pair_min_threshold.insert(&token_pair, &min_threshold);
+            }
+            None => {
+                log!("Removed minimum threshold of {}", token_pair);
+                self.pair_min_threshold.remove(&token_pair);
+            }
+        }
+    }
+
+    /// Recovers NEAR stranded in the contract (e.g. after failed promises).
+    /// Only the free balance can be withdrawn, so profits, treasury earnings,
+    /// intent deposits and storage staking are never touched. Counts towards
+    /// the circuit breaker like any other outflow.
+    pub fn owner_emergency_withdraw(&mut self, amount: U128, to: AccountId) -> Promise {
+        self.assert_owner();
+        let surplus = self.free_balance();
+        assert!(
+            amount.0 <= surplus,
+            "Amount exceeds withdrawable surplus of {}",
+            surplus
+        );
+        self.record_outflow(amount.0);
+
+        log!("Emergency withdrawal of {} to {}", amount.0, to);
+        Promise::new(to).transfer(NearToken::from_yoctonear(amount.0))
+    }
+
+    fn assert_pair_allowed(&self, token_pair: &str) {
+        assert_valid_token_pair(token_pair);
+        let token_pair = normalize_pair(token_pair);
+        let allowed = self.allowed_pairs.iter().any(|pair| normalize_pair(pair) == token_pair);
+        assert!(
+            self.allowed_pairs.is_empty() || allowed,
+            "Token pair {} is not whitelisted",
+            token_pair
+        );
+    }
+
+    fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only contract owner can call this method"
+        );
+    }
+
+    fn assert_not_blocked(&self, account_id: &AccountId) {
+        assert!(!self.is_blocked(account_id.clone()), "Account {} is blocked", account_id);
+    }
+
+    pub fn block_account(&mut self, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        self.blocklist.insert(&account_id, &true);
+        log!("Blocked {}", account_id);
+    }
+
+    pub fn unblock_account(&mut self, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        self.blocklist.remove(&account_id);
+        log!("Unblocked {}", account_id);
+    }
+
+    pub fn is_blocked(&self, account_id: AccountId) -> bool {
+        self.blocklist.get(&account_id).unwrap_or(false)
+    }
+
+    fn assert_role(&self, role: Role) {
+        assert!(
+            self.has_role(env::predecessor_account_id(), role),
+            "Only contract owner or {:?} role can call this method",
+            role
+        );
+    }
+
+    pub fn grant_role(&mut self, account_id: AccountId, role: Role) {
+        self.assert_owner();
+        let roles = self.roles.get(&account_id).unwrap_or(0);
+        self.roles.insert(&account_id, &(roles | role.bit()));
+        log!("Granted {:?} role to {}", role, account_id);
+    }
+
+    pub fn revoke_role(&mut self, account_id: AccountId, role: Role) {
+        self.assert_owner();
+        let roles = self.roles.get(&account_id).unwrap_or(0) & !role.bit();
+        if roles == 0 {
+            self.roles.remove(&account_id);
+        } else {
+            self.roles.insert(&account_id, &roles);
+        }
+        log!("Revoked {:?} role from {}", role, account_id);
+    }
+
+    pub fn has_role(&self, account_id: AccountId, role: Role) -> bool {
+        account_id == self.owner || self.roles.get(&account_id).unwrap_or(0) & role.bit() != 0
+    }
+
+    pub fn get_roles(&self, account_id: AccountId) -> Vec<Role> {
+        Role::ALL
+            .into_iter()
+            .filter(|role| self.has_role(account_id.clone(), *role))
+            .collect()
+    }
+
+    // Arbitrage Execution
+    /// Executes an intent. Submissions carrying an `idempotency_key` the
+    /// caller has already used return the earlier execution id instead of
+    /// executing again, so keepers can safely retry; a failed swap releases
+    /// its key so the retry executes. Any attached deposit is refunded, since
+    /// the swap promise forwards none of it.
+    #[payable]
+    pub fn execute_arbitrage(
+        &mut self,
+        intent_id: String,
+        near_price: String,
+        eth_price: String,
+        price_timestamp: U64,
+        amount: U128,
+        idempotency_key: Option<String>,
+    ) -> PromiseOrValue<String> {
+        let user = env::predecessor_account_id();
+        let attached = env::attached_deposit();
+        if !attached.is_zero() {
+            // Rolled back together with the call if execution fails
+            let _ = Promise::new(user.clone()).transfer(attached);
+            log!("Refunding unused deposit {} to {}", attached.as_yoctonear(), user);
+        }
+
+        // Keys are namespaced by caller so one account can't shadow another's
+        let idempotency_key = idempotency_key.map(|key| format!("{}:{}", user, key));
+        if let Some(execution_id) = self.duplicate_execution(&idempotency_key) {
+            log!("Duplicate submission, returning execution {}", execution_id);
+            return PromiseOrValue::Value(execution_id);
+        }
+
+        self.check_price_age(price_timestamp)
+            .unwrap_or_else(|err| env::panic_str(&err));
+
+        let intent = self.intents.get(&intent_id).expect("Intent not found");
+        let (near_price_f64, eth_price_f64) = self
+            .check_execution(&intent, &user, &near_price, &eth_price, amount.0)
+            .unwrap_or_else(|err| env::panic_str(&err));
+
+        // Conditional intents execute from the callback once the condition holds
+        if let Some(condition) = intent.condition {
+            let check = Promise::new(condition.contract).function_call(
+                condition.method,
+                b"{}".to_vec(),
+                NearToken::from_yoctonear(0),
+                GAS_FOR_CONDITION_CALL,
+            );
+            return PromiseOrValue::Promise(
+                check.then(
+                    Self::ext(env::current_account_id())
+                        .with_static_gas(GAS_FOR_CONDITION_CALLBACK)
+                        .on_condition_checked(
+                            intent_id,
+                            near_price,
+                            eth_price,
+                            amount,
+                            idempotency_key,
+                            user,
+                        ),
+                ),
+            );
+        }
+
+        PromiseOrValue::Promise(self.execute_near_dex_swap(
+            intent_id,
+            near_price_f64,
+            eth_price_f64,
+            amount,
+            idempotency_key,
+            user,
+        ))
+    }
+
+    /// Resumes `execute_arbitrage` for a conditional intent once its
+    /// condition call returns, executing only if the result matches. The key
+    /// is checked again, since a retry may have executed in the meantime.
+    #[private]
+    pub fn on_condition_checked(
+        &mut self,
+        intent_id: String,
+        near_price: String,
+        eth_price: String,
+        amount: U128,
+        idempotency_key: Option<String>,
+        executor: AccountId,
+    ) -> PromiseOrValue<String> {
+        let intent = self.intents.get(&intent_id).expect("Intent not found");
+        let condition = intent.condition.as_ref().expect("Intent has no condition");
+        let result = env::promise_result_checked(0, MAX_CONDITION_RESULT_LEN)
+            .unwrap_or_else(|_| env::panic_str("External condition call failed"));
+        assert!(condition.is_met(&result), "External condition not met");
+        if let Some(execution_id) = self.duplicate_execution(&idempotency_key) {
+            log!("Duplicate submission, returning execution {}", execution_id);
+            return PromiseOrValue::Value(execution_id);
+        }
+
+        // The intent may have changed while the condition was being read
+        let (near_price, eth_price) = self
+            .check_execution(&intent, &executor, &near_price, &eth_price, amount.0)
+            .unwrap_or_else(|err| env::panic_str(&err));
+        PromiseOrValue::Promise(self.execute_near_dex_swap(
+            intent_id,
+            near_price,
+            eth_price,
+            amount,
+            idempotency_key,
+            executor,
+        ))
+    }
+
+    /// Executes at the median `get_price` of the configured oracles instead
+    /// of caller-quoted prices. Each oracle is asked for the intent's pair
+    /// and must return an `OraclePrice` quoting both legs; the execution goes
+    /// ahead only if at least `oracle_quorum` fresh quotes come back.
+    pub fn execute_arbitrage_with_oracles(
+        &mut self,
+        intent_id: String,
+        amount: U128,
+        idempotency_key: Option<String>,
+    ) -> PromiseOrValue<String> {
+        assert!(!self.oracle_accounts.is_empty(), "No oracle accounts configured");
+        let executor = env::predecessor_account_id();
+        let idempotency_key = idempotency_key.map(|key| format!("{}:{}", executor, key));
+        if let Some(execution_id) = self.duplicate_execution(&idempotency_key) {
+            log!("Duplicate submission, returning execution {}", execution_id);
+            return PromiseOrValue::Value(execution_id);
+        }
+
+        let intent = self.intents.get(&intent_id).expect("Intent not found");
+        assert!(
+            intent.condition.is_none(),
+            "Conditional intents must be executed with execute_arbitrage"
+        );
+
+        let args = serde_json::json!({ "token_pair": intent.token_pair }).to_string().into_bytes();
+        let price_requests = self
+            .oracle_accounts
+            .iter()
+            .map(|oracle| {
+                Promise::new(oracle.clone()).function_call(
+                    "get_price".to_string(),
+                    args.clone(),
+                    NearToken::from_yoctonear(0),
+                    GAS_FOR_ORACLE_CALL,
+                )
+            })
+            .reduce(|all, request| all.and(request))
+            .expect("No oracle accounts configured");
+
+        PromiseOrValue::Promise(
+            price_requests.then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ORACLE_CALLBACK)
+                    .on_oracle_prices(intent_id, amount, idempotency_key, executor),
+            ),
+        )
+    }
+
+    /// Joins the oracle responses of `execute_arbitrage_with_oracles` and
+    /// executes at the median of each leg. Failed, unparseable or stale
+    /// quotes don't count towards the quorum. The key is checked again, since
+    /// a retry may have executed while the oracles were being read.
+    #[private]
+    pub fn on_oracle_prices(
+        &mut self,
+        intent_id: String,
+        amount: U128,
+        idempotency_key: Option<String>,
+        executor: AccountId,
+    ) -> PromiseOrValue<String> {
+        let valid = |price: &f64| price.is_finite() && *price > 0.0;
+        let (mut near_prices, mut eth_prices): (Vec<f64>, Vec<f64>) =
+            (0..env::promise_results_count())
+                .filter_map(|index| env::promise_result_checked(index, MAX_ORACLE_RESULT_LEN).ok())
+                .filter_map(|data| serde_json::from_slice::<OraclePrice>(&data).ok())
+                .filter(|quote| self.check_price_age(quote.timestamp).is_ok())
+                .filter_map(|quote| {
+                    let near_price = quote.near_price.parse::<f64>().ok()?;
+                    let eth_price = quote.eth_price.parse::<f64>().ok()?;
+                    Some((near_price, eth_price))
+                })
+                .filter(|(near_price, eth_price)| valid(near_price) && valid(eth_price))
+                .unzip();
+        assert!(
+            near_prices.len() >= self.oracle_quorum as usize,
+            "Only {} oracle prices received, quorum is {}",
+            near_prices.len(),
+            self.oracle_quorum
+        );
+        if let Some(execution_id) = self.duplicate_execution(&idempotency_key) {
+            log!("Duplicate submission, returning execution {}", execution_id);
+            return PromiseOrValue::Value(execution_id);
+        }
+        let near_price = median(&mut near_prices).to_string();
+        let eth_price = median(&mut eth_prices).to_string();
+        log!(
+            "Median oracle prices {} / {} from {} oracles",
+            near_price,
+            eth_price,
+            near_prices.len()
+        );
+
+        let intent = self.intents.get(&intent_id).expect("Intent not found");
+        let (near_price, eth_price) = self
+            .check_execution(&intent, &executor, &near_price, &eth_price, amount.0)
+            .unwrap_or_else(|err| env::panic_str(&err));
+        PromiseOrValue::Promise(self.execute_near_dex_swap(
+            intent_id,
+            near_price,
+            eth_price,
+            amount,
+            idempotency_key,
+            executor,
+        ))
+    }
+
+    /// Executes each entry for the intent's whole remaining notional, with
+    /// the same price age, idempotency and execution checks as
+    /// `execute_arbitrage`. Entries that fail them are skipped and reported
+    /// in a summary log instead of aborting the batch.
+    pub fn execute_arbitrage_batch(&mut self, executions: Vec<BatchExecution>) -> Vec<Promise> {
+        let user = env::predecessor_account_id();
+        let mut promises = Vec::new();
+        let mut skipped = Vec::new();
+
+        for entry in executions {
+            let intent_id = entry.intent_id;
+            let idempotency_key = entry.idempotency_key.map(|key| format!("{}:{}", user, key));
+            if let Some(execution_id) = self.duplicate_execution(&idempotency_key) {
+                skipped.push(format!("{} (Duplicate of execution {})", intent_id, execution_id));
+                continue;
+            }
+            if let Err(err) = self.check_price_age(entry.price_timestamp) {
+                skipped.push(format!("{} ({})", intent_id, err));
+                continue;
+            }
+            let Some(intent) = self.intents.get(&intent_id) else {
+                skipped.push(format!("{} (Intent not found)", intent_id));
+                continue;
+            };
+            if intent.condition.is_some() {
+                skipped.push(format!("{} (Intent has an external condition)", intent_id));
+                continue;
+            }
+            let amount = intent.deposit.0 - intent.executed_amount.0;
+            match self.check_execution(&intent, &user, &entry.near_price, &entry.eth_price, amount)
+            {
+                Ok((near_price, eth_price)) => promises.push(self.execute_near_dex_swap(
+                    intent_id,
+                    near_price,
+                    eth_price,
+                    U128(amount),
+                    idempotency_key,
+                    user.clone(),
+                )),
+                Err(err) => skipped.push(format!("{} ({})", intent_id, err)),
+            }
+        }
+
+        log!(
+            "Batch executed {} intents, skipped {}: [{}]",
+            promises.len(),
+            skipped.len(),
+            skipped.join(", ")
+        );
+        promises
+    }
+
+    /// Execution already recorded under the namespaced `idempotency_key`.
+    fn duplicate_execution(&self, idempotency_key: &Option<String>) -> Option<String> {
+        idempotency_key
+            .as_ref()
+            .and_then(|key| self.executed_keys.get(key))
+    }
+
+    /// Rejects quotes from the future or older than `max_price_age_ns`,
+    /// which could replay stale prices.
+    fn check_price_age(&self, price_timestamp: U64) -> Result<(), String> {
+        if price_timestamp.0 > env::block_timestamp() {
+            return Err("Price timestamp is in the future".to_string());
+        }
+        if self.max_price_age_ns.0 != 0
+            && env::block_timestamp() - price_timestamp.0 > self.max_price_age_ns.0
+        {
+            return Err("Prices are stale".to_string());
+        }
+        Ok(())
+    }
+
+    /// Validates an execution of `amount` by `user` and returns the parsed
+    /// prices, or the reason the execution is not allowed.
+    fn check_execution(
+        &self,
+        intent: &ArbitrageIntent,
+        user: &AccountId,
+        near_price: &str,
+        eth_price: &str,
+        amount: u128,
+    ) -> Result<(f64, f64), String> {
+        if self.paused {
+            return Err("Contract is paused".to_string());
+        }
+        if self.is_blocked(user.clone()) {
+            return Err(format!("Account {} is blocked", user));
+        }
+        if !intent.can_execute(user) {
+            return Err("Only intent owner or an authorized keeper can execute".to_string());
+        }
+        if !matches!(intent.status, IntentStatus::Active) {
+            return Err("Intent must be active".to_string());
+        }
+        if !intent.is_activated() {
+            return Err("Intent not yet active".to_string());
+        }
+        if intent.is_expired() {
+            return Err("Intent expired".to_string());
+        }
+        if amount == 0 {
+            return Err("Amount must be greater than zero".to_string());
+        }
+        if amount > intent.deposit.0 - intent.executed_amount.0 {
+            return Err("Amount exceeds remaining intent notional".to_string());
+        }
+        let since_last_execution = env::block_timestamp() - intent.last_executed_at.0;
+        if intent.last_executed_at.0 != 0 && since_last_execution < self.execution_cooldown_ns.0 {
+            return Err("Cooldown active".to_string());
+        }
+        if intent.last_executed_at.0 != 0
+            && intent
+                .interval_ns
+                .is_some_and(|interval| since_last_execution < interval.0)
+        {
+            return Err("Execution interval not elapsed".to_string());
+        }
+        if intent.max_executions.is_some_and(|max| intent.execution_count >= max) {
+            return Err("Intent execution limit reached".to_string());
+        }
+        if self.max_executions_per_day > 0
+            && self.executions_today(user) >= self.max_executions_per_day
+        {
+            return Err("Daily execution limit reached".to_string());
+        }
+
+        let (near_price, eth_price) = try_parse_prices(near_price, eth_price)?;
+        intent.check_trigger(near_price, eth_price)?;
+        if intent
+            .min_price_diff
+            .is_some_and(|min_price_diff| (near_price - eth_price).abs() < min_price_diff)
+        {
+            return Err("Price difference below minimum".to_string());
+        }
+        if let Some(min_absolute_profit) = intent.min_absolute_profit {
+            let profit = to_yocto(execution_profit(
+                intent,
+                near_price,
+                eth_price,
+                amount,
+                self.profit_factor_bps,
+            ));
+            if profit < min_absolute_profit.0 {
+                return Err("Profit below minimum absolute profit".to_string());
+            }
+        }
+        Ok((near_price, eth_price))
+    }
+
+    fn executions_today(&self, user: &AccountId) -> u32 {
+        let day = env::block_timestamp() / NANOS_PER_DAY;
+        self.daily_executions.get(&(user.clone(), day)).unwrap_or(0)
+    }
+
+    fn execute_near_dex_swap(
+        &mut self,
+        intent_id: String,
+        near_price: f64,
+        eth_price: f64,
+        amount: U128,
+        idempotency_key: Option<String>,
+        executor: AccountId,
+    ) -> Promise {
+        let mut intent = self.intents.get(&intent_id).expect("Intent not found");
+
+        let execution_id = self.next_execution_id_for(&intent.user, &intent_id);
+        let tx_hash = pseudo_tx_hash(self.next_execution_id);
+        self.next_execution_id = self
+            .next_execution_id
+            .checked_add(1)
+            .unwrap_or_else(|| env::panic_str("id counter overflow"));
+
+        // The only place keys are recorded; `settle_swap` releases them
+        if let Some(key) = &idempotency_key {
+            self.executed_keys.insert(key, &execution_id);
+        }
+
+        let executions_today = self.executions_today(&executor) + 1;
+        let day = env::block_timestamp() / NANOS_PER_DAY;
+        self.daily_executions.insert(&(executor.clone(), day), &executions_today);
+
+        let price_diff = (near_price - eth_price).abs();
+        let profit =
+            execution_profit(&intent, near_price, eth_price, amount.0, self.profit_factor_bps);
+        // The setter caps the factor at 100%; never pay out more than the spread
+        assert!(profit <= price_diff, "Profit exceeds price difference");
+        let gas_fees = 0.01; // Placeholder gas fee in NEAR
+
+        let pair_key = normalize_pair(&intent.token_pair);
+        let mut stats = self.pair_stats.get(&pair_key).unwrap_or_default();
+        stats.record((price_diff * PRICE_DIFF_SCALE).round() as i128);
+        self.pair_stats.insert(&pair_key, &stats);
+
+        let execution = ArbitrageExecution {
+            id: execution_id.clone(),
+            intent_id: intent_id.clone(),
+            user: intent.user.clone(),
+            token_pair: intent.token_pair.clone(),
+            price_diff,
+            profit,
+            gas_fees,
+            tx_hash,
+            timestamp: U64(env::block_timestamp()),
+            near_price,
+            eth_price,
+            amount,
+        };
+
+        self.executions.insert(&execution_id, &execution);
+        self.all_execution_ids.push(&execution_id);
+
+        let mut user_execution_list = self.user_executions.get(&intent.user).unwrap_or_else(|| {
+            Vector::new(StorageKey::UserExecutionList {
+                account_hash: env::sha256_array(intent.user.as_bytes()),
+            })
+        });
+        user_execution_list.push(&execution_id);
+        self.user_executions.insert(&intent.user, &user_execution_list);
+
+        let mut intent_execution_list = self.intent_executions.get(&intent_id).unwrap_or_else(|| {
+            Vector::new(StorageKey::IntentExecutionList {
+                intent_hash: env::sha256_array(intent_id.as_bytes()),
+            })
+        });
+        intent_execution_list.push(&execution_id);
+        self.intent_executions.insert(&intent_id, &intent_execution_list);
+
+        self.total_volume = U128(self.total_volume.0 + amount.0);
+
+        intent.executed_amount = U128(intent.executed_amount.0 + amount.0);
+        if intent.funding_token.is_none() {
+            self.intent_deposits = U128(self.intent_deposits.0 - amount.0);
+        }
+        intent.last_executed_at = U64(env::block_timestamp());
+        intent.execution_count += 1;
+        intent.pending_swaps += 1;
+        if intent.executed_amount.0 >= intent.deposit.0 {
+            intent.status = IntentStatus::Executed; // Update intent status
+        }
+        self.intents.insert(&intent_id, &intent);
+
+        log!("Executed arbitrage {} with profit {}", execution_id, profit);
+
+        // Profit is only settled once the swap is known to have gone through.
+        // Only native intents can attach their notional to a DEX call; token
+        // intents and contracts without a DEX settle without a swap.
+        let callback = Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_SWAP_CALLBACK)
+            .on_swap_complete(execution_id, executor, idempotency_key);
+        match intent.dex.or_else(|| self.dex_contract.clone()) {
+            Some(dex) if intent.funding_token.is_none() => Promise::new(dex)
+                .function_call(
+                    "swap".to_string(),
+                    serde_json::json!({ "token_pair": intent.token_pair, "amount_in": amount })
+                        .to_string()
+                        .into_bytes(),
+                    NearToken::from_yoctonear(amount.0),
+                    GAS_FOR_DEX_SWAP,
+                )
+                .then(callback),
+            _ => callback,
+        }
+    }
+
+    /// Settles an execution once its swap resolves. Without a swap to wait
+    /// for there are no promise results and nothing was swapped; otherwise
+    /// the DEX's `swap` result is the yoctoNEAR it sent back for the
+    /// notional. The notional already left with the call, so an unreadable
+    /// result settles as nothing sent back.
+    #[private]
+    pub fn on_swap_complete(
+        &mut self,
+        execution_id: String,
+        executor: AccountId,
+        idempotency_key: Option<String>,
+    ) -> PromiseOrValue<bool> {
+        let swap_result = if env::promise_results_count() == 0 {
+            Ok(None)
+        } else {
+            env::promise_result_checked(0, MAX_SWAP_RESULT_LEN).map(|data| {
+                let output = parse_swap_output(&data);
+                if output.is_none() {
+                    log!("Unreadable swap output for execution {}", execution_id);
+                }
+                Some(output.unwrap_or(0))
+            })
+        };
+        self.settle_swap(execution_id, executor, idempotency_key, swap_result)
+    }
+
+    /// With a swap output, the DEX is trusted to have sent it back by the
+    /// time its call resolved. The notional it covers goes back to the user
+    /// with the profit (output minus notional), split with the treasury,
+    /// referrer and keeper. Without one, nothing was swapped: the simulated
+    /// profit is only recorded, as before real swaps, and the notional is
+    /// returned. On failure the DEX refunded the notional, which is released
+    /// back to the intent, the idempotency key is freed for a retry, and
+    /// nothing is paid.
+    fn settle_swap(
+        &mut self,
+        execution_id: String,
+        executor: AccountId,
+        idempotency_key: Option<String>,
+        swap_result: Result<Option<u128>, PromiseError>,
+    ) -> PromiseOrValue<bool> {
+        let mut execution = self.executions.get(&execution_id).expect("Execution not found");
+        // `cancel_intent` waits for pending swaps, so this only trips on state
+        // written before they were tracked; losing the payout beats a panic
+        // that would leave the execution half-settled
+        let Some(mut intent) = self.intents.get(&execution.intent_id) else {
+            log!(
+                "Intent {} of execution {} no longer exists, nothing settled",
+                execution.intent_id,
+                execution_id
+            );
+            return PromiseOrValue::Value(false);
+        };
+        intent.pending_swaps = intent.pending_swaps.saturating_sub(1);
+
+        if swap_result.is_err() {
+            intent.executed_amount = U128(intent.executed_amount.0 - execution.amount.0);
+            if intent.funding_token.is_none() {
+                self.intent_deposits = U128(self.intent_deposits.0 + execution.amount.0);
+            }
+            if matches!(intent.status, IntentStatus::Executed) {
+                intent.status = IntentStatus::Active;
+            }
+            intent.execution_count -= 1;
+            intent.consecutive_failures += 1;
+            if self.max_consecutive_failures > 0
+                && intent.consecutive_failures >= self.max_consecutive_failures
+            {
+                intent.status = IntentStatus::Paused;
+                intent.paused_at = Some(U64(env::block_timestamp()));
+                log!(
+                    "Paused intent {} after {} consecutive failures",
+                    execution.intent_id,
+                    intent.consecutive_failures
+                );
+            }
+            self.intents.insert(&execution.intent_id, &intent);
+            self.total_volume = U128(self.total_volume.0 - execution.amount.0);
+            if let Some(key) = idempotency_key {
+                self.executed_keys.remove(&key);
+            }
+            log!("Swap for execution {} failed, no profit settled", execution_id);
+            return PromiseOrValue::Value(false);
+        }
+        intent.consecutive_failures = 0;
+        self.intents.insert(&execution.intent_id, &intent);
+        self.settled_executions += 1;
+        let native = env::current_account_id();
+        // Token intents never swap, whatever the result claims
+        let output = swap_result.ok().flatten().filter(|_| intent.funding_token.is_none());
+        let Some(output) = output else {
+            log!("Settled execution {} without a swap, returning the notional", execution_id);
+            let payout = match intent.funding_token.clone() {
+                Some(token_id) => {
+                    self.credit_claimable_profit(&intent.user, &token_id, execution.amount.0);
+                    None
+                }
+                None => {
+                    self.add_user_profit(&intent.user, &native, to_yocto(execution.profit));
+                    self.pay_out_native(intent.user.clone(), execution.amount.0)
+                }
+            };
+            return match payout {
+                Some(promise) => PromiseOrValue::Promise(promise),
+                None => PromiseOrValue::Value(true),
+            };
+        };
+
+        log!("Swap for execution {} returned {}", execution_id, output);
+        // Intents pick their own DEX, so its report may lower the bounded
+        // simulated profit but never raise it
+        let principal = output.min(execution.amount.0);
+        let profit_amount =
+            output.saturating_sub(execution.amount.0).min(to_yocto(execution.profit));
+        if profit_amount < to_yocto(execution.profit) {
+            execution.profit = to_near(profit_amount);
+            self.executions.insert(&execution_id, &execution);
+        }
+
+        // Split the gross profit; the user takes the remainder so the three
+        // shares always add up to the gross amount exactly.
+        let owner_fee = profit_amount * self.fee_basis_points as u128 / 10_000;
+        let referrer_fee = match &intent.referrer {
+            Some(referrer) => {
+                let cut = profit_amount * self.referrer_bps as u128 / 10_000;
+                let earned = self.referrer_earnings.get(referrer).unwrap_or(U128(0));
+                self.referrer_earnings.insert(referrer, &U128(earned.0 + cut));
+                self.reserved_profits = U128(self.reserved_profits.0 + cut);
+                cut
+            }
+            None => 0,
+        };
+        self.treasury_earnings = U128(self.treasury_earnings.0 + owner_fee);
+        // A keeper's cut comes out of the user's share; none when self-executed
+        let user_share = profit_amount - owner_fee - referrer_fee;
+        let keeper_fee = if executor != intent.user {
+            user_share * intent.keeper_fee_bps as u128 / 10_000
+        } else {
+            0
+        };
+        let user_profit = user_share - keeper_fee;
+
+        self.add_user_profit(&intent.user, &native, user_profit);
+        self.total_profit_paid = U128(self.total_profit_paid.0 + profit_amount);
+
+        let mut native_payout = principal + user_profit;
+        let pair_key = normalize_pair(&intent.token_pair);
+        if !self.pairs_seen.get(&pair_key).unwrap_or(false) {
+            self.pairs_seen.insert(&pair_key, &true);
+            let bonus = self.first_execution_bonus.0;
+            if bonus > 0 {
+                self.add_user_profit(&intent.user, &native, bonus);
+                self.total_profit_paid = U128(self.total_profit_paid.0 + bonus);
+                native_payout += bonus;
+                log!("First execution bonus {} on {} to {}", bonus, intent.token_pair, intent.user);
+            }
+        }
+
+        let payout = if native_payout > 0 {
+            self.pay_out_native(intent.user.clone(), native_payout)
+        } else {
+            None
+        };
+        if keeper_fee > 0 {
+            log!("Paid keeper fee {} to {}", keeper_fee, executor);
+            // A joint promise can't be returned, so the keeper's is sent on
+            // its own
+            let _ = self.pay_out_native(executor, keeper_fee);
+        }
+
+        log!("Settled execution {} with profit {}", execution_id, user_profit);
+        match payout {
+            Some(promise) => PromiseOrValue::Promise(promise),
+            None => PromiseOrValue::Value(true),
+        }
+    }
+
+    /// Transfers native profit straight away if the free balance covers it
+    /// and the reserve holds afterwards, counting it towards the circuit
+    /// breaker. Otherwise, or while paused, it is credited as claimable and
+    /// `withdraw_all_profits` applies the same checks later.
+    fn pay_out_native(&mut self, receiver: AccountId, amount: u128) -> Option<Promise> {
+        if self.paused
+            || amount > self.free_balance()
+            || self.check_reserve_after(amount, 0).is_err()
+        {
+            log!("Holding {} of profit for {} as claimable", amount, receiver);
+            self.credit_claimable_profit(&receiver, &env::current_account_id(), amount);
+            return None;
+        }
+        self.record_outflow(amount);
+        Some(pay_out(&env::current_account_id(), receiver, amount))
+    }
+
+    /// Execution ids are the hex sha256 of (user, intent_id, nonce, block
+    /// timestamp) rather than a global sequence, so they reveal nothing about
+    /// protocol activity and can't be guessed.
+    fn next_execution_id_for(&mut self, user: &AccountId, intent_id: &str) -> String {
+        let nonce = self.user_execution_nonces.get(user).unwrap_or(0);
+        self.user_execution_nonces.insert(user, &(nonce + 1));
+
+        let preimage = borsh::to_vec(&(user, intent_id, nonce, env::block_timestamp()))
+            .expect("Failed to serialize execution id preimage");
+        hex::encode(env::sha256(&preimage))
+    }
+
+    fn add_user_profit(&mut self, user: &AccountId, token_id: &AccountId, amount: u128) {
+        let key = (user.clone(), token_id.clone());
+        let total = match self.user_profits.get(&key) {
+            Some(total) => total.0,
+            None => {
+                let mut tokens = self.user_earned_tokens.get(user).unwrap_or_else(|| {
+                    Vector::new(StorageKey::UserEarnedTokenList {
+                        account_hash: env::sha256_array(user.as_bytes()),
+                    })
+                });
+                tokens.push(token_id);
+                self.user_earned_tokens.insert(user, &tokens);
+                0
+            }
+        };
+        self.user_profits.insert(&key, &U128(total + amount));
+    }
+
+    fn credit_claimable_profit(&mut self, user: &AccountId, token_id: &AccountId, amount: u128) {
+        let key = (user.clone(), token_id.clone());
+        let balance = self.claimable_profits.get(&key).unwrap_or(U128(0));
+        if balance.0 == 0 {
+            let mut tokens = self.user_profit_tokens.get(user).unwrap_or_else(|| {
+                Vector::new(StorageKey::UserProfitTokenList {
+                    account_hash: env::sha256_array(user.as_bytes()),
+                })
+            });
+            tokens.push(token_id);
+            self.user_profit_tokens.insert(user, &tokens);
+        }
+        self.claimable_profits.insert(&key, &U128(balance.0 + amount));
+        if *token_id == env::current_account_id() {
+            self.reserved_profits = U128(self.reserved_profits.0 + amount);
+        }
+    }
+
+    // Profit Withdrawal
+    /// Pays out every non-zero claimable balance of the caller: native NEAR
+    /// via a transfer and NEP-141 tokens via `ft_transfer` on the token.
+    pub fn withdraw_all_profits(&mut self) -> Vec<Promise> {
+        assert!(!self.paused, "Contract is paused");
+        let user = env::predecessor_account_id();
+        self.assert_not_blocked(&user);
+        let mut promises = Vec::new();
+
+        let Some(mut tokens) = self.user_profit_tokens.get(&user) else {
+            return promises;
+        };
+        let native = env::current_account_id();
+        let native_withdrawn = self.get_claimable_profit(user.clone(), native.clone()).0;
+        self.reserved_profits = U128(self.reserved_profits.0 - native_withdrawn);
+        self.assert_reserve_after(native_withdrawn);
+        self.record_outflow(native_withdrawn);
+
+        for token_id in tokens.iter() {
+            let amount = self
+                .claimable_profits
+                .remove(&(user.clone(), token_id.clone()))
+                .unwrap_or(U128(0));
+            if amount.0 == 0 {
+                continue;
+            }
+
+            let promise = pay_out(&token_id, user.clone(), amount.0).then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_WITHDRAW_CALLBACK)
+                    .on_withdraw_resolved(user.clone(), token_id.clone(), amount),
+            );
+            log!("Withdrew {} of {} profit for {}", amount.0, token_id, user);
+            promises.push(promise);
+        }
+        tokens.clear();
+        self.user_profit_tokens.insert(&user, &tokens);
+
+        promises
+    }
+
+    /// Pays out the caller's accumulated referral earnings in native NEAR.
+    pub fn withdraw_referrer_earnings(&mut self) -> Promise {
+        assert!(!self.paused, "Contract is paused");
+        let referrer = env::predecessor_account_id();
+        self.assert_not_blocked(&referrer);
+        let amount = self.referrer_earnings.remove(&referrer).unwrap_or(U128(0));
+        assert!(amount.0 > 0, "No referrer earnings to withdraw");
+        self.record_outflow(amount.0);
+
+        self.reserved_profits = U128(self.reserved_profits.0 - amount.0);
+        self.assert_reserve_after(amount.0);
+        log!("Withdrew {} referrer earnings for {}", amount.0, referrer);
+        Promise::new(referrer.clone()).transfer(NearToken::from_yoctonear(amount.0)).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_WITHDRAW_CALLBACK)
+                .on_referrer_withdraw_resolved(referrer, amount),
+        )
+    }
+
+    /// Emits `profit_withdrawn` once a profit withdrawal went through, or
+    /// credits it back as claimable if its transfer failed.
+    #[private]
+    pub fn on_withdraw_resolved(
+        &mut self,
+        user: AccountId,
+        token_id: AccountId,
+        amount: U128,
+    ) -> bool {
+        if env::promise_result_checked(0, MAX_PAYOUT_RESULT_LEN).is_ok() {
+            emit_event(
+                "profit_withdrawn",
+                serde_json::json!({
+                    "account_id": user,
+                    "token_id": token_id,
+                    "amount": amount,
+                    "remaining": self.get_claimable_profit(user.clone(), token_id.clone()),
+                }),
+            );
+            return true;
+        }
+        self.credit_claimable_profit(&user, &token_id, amount.0);
+        log!("Withdrawal of {} of {} for {} failed, restored", amount.0, token_id, user);
+        false
+    }
+
+    /// Emits `profit_withdrawn` once a referrer withdrawal went through, or
+    /// restores the earnings if its transfer failed.
+    #[private]
+    pub fn on_referrer_withdraw_resolved(&mut self, referrer: AccountId, amount: U128) -> bool {
+        if env::promise_result_checked(0, MAX_PAYOUT_RESULT_LEN).is_ok() {
+            emit_event(
+                "profit_withdrawn",
+                serde_json::json!({
+                    "account_id": referrer,
+                    "token_id": env::current_account_id(),
+                    "amount": amount,
+                    "remaining": self.get_referrer_earnings(referrer.clone()),
+                }),
+            );
+            return true;
+        }
+        let earned = self.referrer_earnings.get(&referrer).unwrap_or(U128(0));
+        self.referrer_earnings.insert(&referrer, &U128(earned.0 + amount.0));
+        self.reserved_profits = U128(self.reserved_profits.0 + amount.0);
+        log!("Withdrawal of {} referrer earnings for {} failed, restored", amount.0, referrer);
+        false
+    }
+
+    /// Checks that once `withdrawn` yoctoNEAR leaves, the balance still covers
+    /// `reserve_ratio_bps` of the NEAR owed to users, referrers and the
+    /// treasury, unexecuted intent deposits included. Call after the withdrawn
+    /// amount is taken off those totals.
+    fn assert_reserve_after(&self, withdrawn: u128) {
+        self.check_reserve_after(withdrawn, 0).unwrap_or_else(|err| env::panic_str(&err));
+    }
+
+    /// Whether the balance covers the reserve once `withdrawn` leaves and
+    /// `released` of it stops being owed.
+    fn check_reserve_after(&self, withdrawn: u128, released: u128) -> Result<(), String> {
+        if self.reserve_ratio_bps == 0 || withdrawn == 0 {
+            return Ok(());
+        }
+        let tracked = self.tracked_liabilities().saturating_sub(released);
+        let required = tracked * self.reserve_ratio_bps as u128 / 10_000;
+        let remaining = env::account_balance().as_yoctonear().saturating_sub(withdrawn);
+        if remaining < required {
+            return Err(format!(
+                "Withdrawal would leave {} below the required reserve of {}",
+                remaining, required
+            ));
+        }
+        Ok(())
+    }
+
+    /// NEAR the contract owes: claimable profits, referrer earnings, the
+    /// treasury's balance and unexecuted intent deposits.
+    fn tracked_liabilities(&self) -> u128 {
+        self.reserved_profits.0 + self.treasury_earnings.0 + self.intent_deposits.0
+    }
+
+    /// Balance left once the tracked liabilities and storage staking are set
+    /// aside, i.e. what can be paid out without touching anyone's funds.
+    fn free_balance(&self) -> u128 {
+        let storage_reserved =
+            env::storage_usage() as u128 * env::storage_byte_cost().as_yoctonear();
+        env::account_balance()
+            .as_yoctonear()
+            .saturating_sub(self.tracked_liabilities() + storage_reserved)
+    }
+
+    /// Whether `user` could withdraw `amount` of native profit now: the
+    /// contract is not paused, the user is not blocked, `amount` is within
+    /// their claimable profit, and the balance covers it and the reserve.
+    pub fn can_withdraw(&self, user: AccountId, amount: U128) -> bool {
+        if self.paused || self.is_blocked(user.clone()) {
+            return false;
+        }
+        let claimable = self.get_claimable_profit(user, env::current_account_id());
+        amount.0 <= claimable.0
+            && amount.0 <= env::account_balance().as_yoctonear()
+            && self.check_reserve_after(amount.0, amount.0).is_ok()
+    }
+
+    /// Adds a native withdrawal to the rolling outflow window and pauses the
+    /// contract once the window total exceeds `circuit_breaker_threshold`.
+    /// Unpausing takes the Pauser role, via `set_paused`.
+    fn record_outflow(&mut self, amount: u128) {
+        if self.circuit_breaker_threshold.0 == 0 {
+            return;
+        }
+        let now = env::block_timestamp();
+        if now - self.outflow_window_start.0 >= NANOS_PER_DAY {
+            self.outflow_window_start = U64(now);
+            self.outflow_window_amount = U128(0);
+        }
+        self.outflow_window_amount = U128(self.outflow_window_amount.0 + amount);
+        if self.outflow_window_amount.0 > self.circuit_breaker_threshold.0 {
+            self.paused = true;
+            log!(
+                "Circuit breaker tripped: {} withdrawn in window, threshold {}",
+                self.outflow_window_amount.0,
+                self.circuit_breaker_threshold.0
+            );
+        }
+    }
+
+    pub fn set_treasury(&mut self, treasury: AccountId) {
+        self.assert_role(Role::FeeManager);
+        log!("Set treasury to {}", treasury);
+        self.treasury = treasury;
+    }
+
+    pub fn withdraw_treasury(&mut self) -> Promise {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.treasury,
+            "Only treasury can withdraw treasury earnings"
+        );
+        assert!(!self.paused, "Contract is paused");
+        let amount = self.treasury_earnings.0;
+        assert!(amount > 0, "No treasury earnings to withdraw");
+        self.record_outflow(amount);
+
+        self.treasury_earnings = U128(0);
+        self.assert_reserve_after(amount);
+        log!("Withdrew {} treasury earnings", amount);
+        let treasury = self.treasury.clone();
+        Promise::new(treasury.clone()).transfer(NearToken::from_yoctonear(amount)).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_WITHDRAW_CALLBACK)
+                .on_treasury_withdraw_resolved(treasury, U128(amount)),
+        )
+    }
+
+    /// Emits `treasury_withdrawn` once a treasury withdrawal went through, or
+    /// restores the earnings if its transfer failed.
+    #[private]
+    pub fn on_treasury_withdraw_resolved(&mut self, treasury: AccountId, amount: U128) -> bool {
+        if env::promise_result_checked(0, MAX_PAYOUT_RESULT_LEN).is_ok() {
+            emit_event(
+                "treasury_withdrawn",
+                serde_json::json!({
+                    "treasury": treasury,
+                    "amount": amount,
+                    "remaining": self.treasury_earnings,
+                }),
+            );
+            return true;
+        }
+        self.treasury_earnings = U128(self.treasury_earnings.0 + amount.0);
+        log!("Withdrawal of {} treasury earnings failed, restored", amount.0);
+        false
+    }
+
+    // Cross-Chain Signature Management
+    pub fn store_cross_chain_signature(
+        &mut self,
+        execution_id: String,
+        signature: Base64VecU8,
+        public_key: PublicKey,
+        chain_id: u64,
+        nonce: u64,
+    ) {
+        assert!(
+            self.supported_chains.get(&chain_id).unwrap_or(false),
+            "Unsupported chain_id {}",
+            chain_id
+        );
+        let curve_type = public_key.as_bytes()[0];
+        assert!(
+            self.allowed_curve_types.contains(&curve_type),
+            "Unsupported public key curve type {}",
+            curve_type
+        );
+        let cross_chain_sig = CrossChainSignature {
+            signature,
+            public_key,
+            chain_id,
+            nonce,
+        };
+
+        self.cross_chain_signatures.insert(&execution_id, &cross_chain_sig);
+        log!("Stored cross-chain signature for execution {}", execution_id);
+        emit_event(
+            "signature_stored",
+            serde_json::json!({
+                "execution_id": execution_id,
+                "chain_id": chain_id,
+                "nonce": nonce,
+            }),
+        );
+    }
+
+    /// Whether the stored signature of an execution is a valid ED25519
+    /// signature of its `signature_message`. False when none is stored or
+    /// the key is on another curve.
+    fn is_valid_signature(&self, execution_id: &str) -> bool {
+        let Some(stored) = self.cross_chain_signatures.get(&execution_id.to_string()) else {
+            return false;
+        };
+        let key = stored.public_key.as_bytes();
+        if key[0] != CURVE_TYPE_ED25519 {
+            return false;
+        }
+        let (Ok(signature), Ok(public_key)) = (
+            <[u8; 64]>::try_from(stored.signature.0.as_slice()),
+            <[u8; 32]>::try_from(&key[1..]),
+        ) else {
+            return false;
+        };
+        let message = signature_message(execution_id, stored.chain_id, stored.nonce);
+        env::ed25519_verify(&signature, message, &public_key)
+    }
+
+    pub fn verify_cross_chain_signature(&self, execution_id: String) -> bool {
+        let result = self.is_valid_signature(&execution_id);
+        emit_event(
+            "signature_verified",
+            serde_json::json!({ "execution_id": execution_id, "result": result }),
+        );
+        result
+    }
+
+    /// `verify_cross_chain_signature` for up to `MAX_SIGNATURE_BATCH`
+    /// executions, in the order given.
+    pub fn verify_cross_chain_signatures_batch(&self, execution_ids: Vec<String>) -> Vec<bool> {
+        assert!(
+            execution_ids.len() <= MAX_SIGNATURE_BATCH,
+            "At most {} signatures can be verified per call",
+            MAX_SIGNATURE_BATCH
+        );
+        execution_ids
+            .iter()
+            .map(|execution_id| self.is_valid_signature(execution_id))
+            .collect()
+    }
+
+    // View Methods
+    pub fn get_user_intents(&self, user: AccountId) -> Vec<ArbitrageIntent> {
+        let mut intents = Vec::new();
+
+        if let Some(user_intent_list) = self.user_intents.get(&user) {
+            for i in 0..user_intent_list.len() {
+                if let Some(intent_id) = user_intent_list.get(i) {
+                    if let Some(intent) = self.intents.get(&intent_id) {
+                        intents.push(intent);
+                    }
+                }
+            }
+        }
+
+        intents
+    }
+
+    /// Distinct token pairs the user has at least one active intent on.
+    pub fn get_user_active_pairs(&self, user: AccountId) -> Vec<String> {
+        let mut pairs: Vec<String> = Vec::new();
+
+        for intent in self.get_user_intents(user) {
+            if matches!(intent.status, IntentStatus::Active) && !pairs.contains(&intent.token_pair) {
+                pairs.push(intent.token_pair);
+            }
+        }
+
+        pairs
+    }
+
+    /// The user's intents that can execute right now: `Active`, past their
+    /// `activate_at` and not yet at their `expires_at`, if set.
+    pub fn get_active_intents(&self, user: AccountId) -> Vec<ArbitrageIntent> {
+        self.get_user_intents(user)
+            .into_iter()
+            .filter(|intent| {
+                matches!(intent.status, IntentStatus::Active)
+                    && intent.is_activated()
+                    && !intent.is_expired()
+            })
+            .collect()
+    }
+
+    /// Intents created in transactions signed with `pk`, at most `limit`
+    /// (capped at 100) from `from_index`, oldest first.
+    pub fn get_intents_by_public_key(
+        &self,
+        pk: PublicKey,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<ArbitrageIntent> {
+        let Some(intent_ids) = self.pk_intents.get(&pk) else {
+            return Vec::new();
+        };
+        let to_index = from_index.saturating_add(limit.min(100)).min(intent_ids.len());
+        (from_index..to_index)
+            .filter_map(|index| intent_ids.get(index))
+            .filter_map(|intent_id| self.intents.get(&intent_id))
+            .collect()
+    }
+
+    pub fn get_execution_history(&self, user: AccountId) -> Vec<ArbitrageExecution> {
+        let mut executions = Vec::new();
+
+        if let Some(user_execution_list) = self.user_executions.get(&user) {
+            for i in 0..user_execution_list.len() {
+                if let Some(execution_id) = user_execution_list.get(i) {
+                    if let Some(execution) = self.executions.get(&execution_id) {
+                        executions.push(execution);
+                    }
+                }
+            }
+        }
+
+        executions
+    }
+
+    /// Timestamp of the user's most recent execution, if any.
+    pub fn get_last_execution_time(&self, user: AccountId) -> Option<U64> {
+        let user_execution_list = self.user_executions.get(&user)?;
+        let execution_id = user_execution_list.get(user_execution_list.len().checked_sub(1)?)?;
+        self.executions.get(&execution_id).map(|execution| execution.timestamp)
+    }
+
+    /// The user's intents worth a look, each with the reason: `"expiring_soon"`
+    /// when it expires within `attention_window_ns`, `"long_paused"` when
+    /// paused for at least that long, or `"never_executed"` when active that
+    /// long without executing. Only the first matching reason is reported.
+    pub fn get_intents_needing_attention(&self, user: AccountId) -> Vec<(String, String)> {
+        let now = env::block_timestamp();
+        let window = self.attention_window_ns.0;
+        self.get_user_intents(user)
+            .into_iter()
+            .filter_map(|intent| {
+                let reason = match intent.status {
+                    IntentStatus::Executed => return None,
+                    _ if intent.is_expired() => return None,
+                    _ if intent
+                        .expires_at
+                        .is_some_and(|expires_at| expires_at.0.saturating_sub(now) <= window) =>
+                    {
+                        "expiring_soon"
+                    }
+                    IntentStatus::Paused
+                        if intent
+                            .paused_at
+                            .is_some_and(|paused_at| now.saturating_sub(paused_at.0) >= window) =>
+                    {
+                        "long_paused"
+                    }
+                    IntentStatus::Active
+                        if intent.last_executed_at.0 == 0
+                            && now.saturating_sub(intent.created_at.0) >= window =>
+                    {
+                        "never_executed"
+                    }
+                    _ => return None,
+                };
+                Some((intent.id, reason.to_string()))
+            })
+            .collect()
+    }
+
+    /// Up to `limit` (capped at 100) of the user's intents in creation order,
+    /// oldest first when `ascending` and newest first otherwise. `from_index`
+    /// counts from the first intent in the chosen order.
+    pub fn get_user_intents_sorted(
+        &self,
+        user: AccountId,
+        ascending: bool,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<ArbitrageIntent> {
+        let Some(intent_ids) = self.user_intents.get(&user) else {
+            return Vec::new();
+        };
+        let len = intent_ids.len();
+        let to_index = from_index.saturating_add(limit.min(100)).min(len);
+        (from_index..to_index)
+            .map(|index| if ascending { index } else { len - 1 - index })
+            .filter_map(|index| intent_ids.get(index))
+            .filter_map(|intent_id| self.intents.get(&intent_id))
+            .collect()
+    }
+
+    /// The user's executions whose profit exceeds `min_profit` (yoctoNEAR),
+    /// scanning at most `limit` (capped at 100) entries of their execution
+    /// list from `from_index`. Page by advancing `from_index` by `limit`.
+    pub fn get_executions_above_profit(
+        &self,
+        user: AccountId,
+        min_profit: U128,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<ArbitrageExecution> {
+        let Some(execution_ids) = self.user_executions.get(&user) else {
+            return Vec::new();
+        };
+        let to_index = from_index.saturating_add(limit.min(100)).min(execution_ids.len());
+        (from_index..to_index)
+            .filter_map(|index| execution_ids.get(index))
+            .filter_map(|execution_id| self.executions.get(&execution_id))
+            .filter(|execution| to_yocto(execution.profit) > min_profit.0)
+            .collect()
+    }
+
+    /// A user's intents and executions from position `from_index` of each
+    /// list, at most `limit` (capped at 100) of each. Page by advancing
+    /// `from_index` by `limit` until both lists come back short.
+    pub fn export_user_data(&self, user: AccountId, from_index: u64, limit: u64) -> UserExport {
+        let limit = limit.min(100);
+        let page = |ids: Option<Vector<String>>| -> Vec<String> {
+            let Some(ids) = ids else {
+                return Vec::new();
+            };
+            let to_index = from_index.saturating_add(limit).min(ids.len());
+            (from_index..to_index).filter_map(|index| ids.get(index)).collect()
+        };
+
+        UserExport {
+            intents: page(self.user_intents.get(&user))
+                .iter()
+                .filter_map(|intent_id| self.intents.get(intent_id))
+                .collect(),
+            executions: page(self.user_executions.get(&user))
+                .iter()
+                .filter_map(|execution_id| self.executions.get(execution_id))
+                .collect(),
+            total_profit: self.get_total_profit(user, env::current_account_id()),
+        }
+    }
+
+    /// All executions of one intent, oldest first. Execution records are
+    /// already public through `get_execution`, so this is readable by anyone.
+    pub fn get_executions_for_intent(&self, intent_id: String) -> Vec<ArbitrageExecution> {
+        let mut executions = Vec::new();
+
+        if let Some(intent_execution_list) = self.intent_executions.get(&intent_id) {
+            for i in 0..intent_execution_list.len() {
+                if let Some(execution_id) = intent_execution_list.get(i) {
+                    if let Some(execution) = self.executions.get(&execution_id) {
+                        executions.push(execution);
+                    }
+                }
+            }
+        }
+
+        executions
+    }
+
+    /// Lifetime profit of `user` in `token_id`; pass the contract's own
+    /// account id for native NEAR.
+    pub fn get_total_profit(&self, user: AccountId, token_id: AccountId) -> U128 {
+        let key = (user.clone(), token_id.clone());
+        let mut profit = self.user_profits.get(&key).unwrap_or(U128(0)).0;
+        if token_id == env::current_account_id() {
+            profit += self.legacy_user_profits.get(&user).unwrap_or(U128(0)).0;
+        }
+        U128(profit)
+    }
+
+    /// Realized profit of a user in every token they have earned in, in the
+    /// order first earned. Pre-migration NEAR profit is listed as native.
+    pub fn get_total_profit_all_tokens(&self, user: AccountId) -> Vec<(AccountId, U128)> {
+        let native = env::current_account_id();
+        let mut tokens: Vec<AccountId> = self
+            .user_earned_tokens
+            .get(&user)
+            .map(|tokens| tokens.to_vec())
+            .unwrap_or_default();
+        if !tokens.contains(&native) && self.legacy_user_profits.get(&user).is_some() {
+            tokens.push(native);
+        }
+        tokens
+            .into_iter()
+            .map(|token_id| (token_id.clone(), self.get_total_profit(user.clone(), token_id)))
+            .collect()
+    }
+
+    /// Realized NEAR profit per execution of the user, rounded down; 0 before
+    /// their first execution.
+    pub fn get_average_profit(&self, user: AccountId) -> U128 {
+        let count = self.user_executions.get(&user).map_or(0, |executions| executions.len());
+        if count == 0 {
+            return U128(0);
+        }
+        U128(self.get_total_profit(user, env::current_account_id()).0 / count as u128)
+    }
+
+    pub fn get_profit_for_epoch(&self, user: AccountId, epoch: u32) -> Option<U128> {
+        self.profit_epochs.get(&(user, epoch))
+    }
+
+    /// Realized profit relative to the NEAR deposited across all of a user's
+    /// intents, in basis points. Returns "0" if the user never deposited.
+    pub fn get_user_roi(&self, user: AccountId) -> String {
+        let deposited = self.total_deposited.get(&user).unwrap_or(U128(0)).0;
+        if deposited == 0 {
+            return "0".to_string();
+        }
+        let profit = self.get_total_profit(user, env::current_account_id()).0;
+        (profit * 10_000 / deposited).to_string()
+    }
+
+    pub fn get_execution_cooldown(&self) -> U64 {
+        self.execution_cooldown_ns
+    }
+
+    /// Population standard deviation of the price diffs executed on a pair,
+    /// scaled by 10^6 like the diffs it is computed from. None before the
+    /// pair's first execution.
+    pub fn get_pair_volatility(&self, token_pair: String) -> Option<String> {
+        let stats = self
+            .pair_stats
+            .get(&normalize_pair(&token_pair))
+            .filter(|stats| stats.count > 0)?;
+        let variance = stats.m2.max(0) as u128 / stats.count as u128;
+        Some(variance.isqrt().to_string())
+    }
+
+    pub fn get_template(&self, name: String) -> Option<IntentTemplate> {
+        self.templates.get(&name)
+    }
+
+    pub fn get_max_active_intents_per_user(&self) -> u32 {
+        self.max_active_intents_per_user
+    }
+
+    pub fn get_max_price_age(&self) -> U64 {
+        self.max_price_age_ns
+    }
+
+    /// Upper bound in yoctoNEAR on the gas an execution burns, priced at the
+    /// protocol minimum gas price.
+    pub fn estimate_execution_cost(&self) -> U128 {
+        let gas = GAS_FOR_DEX_SWAP.as_gas() + GAS_FOR_CROSS_CHAIN_CALL.as_gas();
+        U128(gas as u128 * GAS_PRICE_YOCTO)
+    }
+
+    /// Smallest price difference, scaled by 10^6 like `pair_stats`, at which
+    /// executing `notional` yoctoNEAR on `token_pair` nets a positive profit
+    /// after `estimate_execution_cost` and the contract fee. Assumes an
+    /// intent funded with exactly `notional`, executed by its owner and
+    /// without a referrer; `min_profitable_spread_for_intent` accounts for
+    /// a specific intent's cuts.
+    pub fn min_profitable_spread(&self, token_pair: String, notional: U128) -> U128 {
+        self.assert_pair_allowed(&token_pair);
+        assert!(notional.0 > 0, "notional must be positive");
+        let user_share = (10_000 - self.fee_basis_points) as f64 / 10_000.0;
+        self.break_even_spread(notional.0, 1.0, user_share)
+    }
+
+    /// `min_profitable_spread` for executing `notional` of an intent, also
+    /// taking the referrer's share if the intent has one and the intent's
+    /// keeper fee.
+    pub fn min_profitable_spread_for_intent(&self, intent_id: String, notional: U128) -> U128 {
+        let intent = self.intents.get(&intent_id).expect("Intent not found");
+        assert!(notional.0 > 0, "notional must be positive");
+        assert!(notional.0 <= intent.deposit.0, "notional exceeds the intent's deposit");
+        let referrer_bps = if intent.referrer.is_some() { self.referrer_bps } else { 0 };
+        let user_share = (10_000 - self.fee_basis_points - referrer_bps) as f64 / 10_000.0
+            * (10_000 - intent.keeper_fee_bps) as f64
+            / 10_000.0;
+        let share_of_deposit = notional.0 as f64 / intent.deposit.0 as f64;
+        self.break_even_spread(notional.0, share_of_deposit, user_share)
+    }
+
+    /// Spread at which `user_share` of the profit on `notional`, a
+    /// `share_of_deposit` fraction of its intent, covers the execution cost.
+    fn break_even_spread(&self, notional: u128, share_of_deposit: f64, user_share: f64) -> U128 {
+        let cost = to_near(self.estimate_execution_cost().0);
+        // Profit is capped at the notional, so no spread can beat a cost above it
+        assert!(
+            self.profit_factor_bps > 0 && to_near(notional) * user_share > cost,
+            "No spread is profitable with the current fee settings"
+        );
+        // Below that cap execution_profit is linear in the spread, with this slope
+        let profit_per_unit_spread = self.profit_factor_bps as f64 / 10_000.0 * share_of_deposit;
+        let spread = cost / (user_share * profit_per_unit_spread);
+        U128((spread * PRICE_DIFF_SCALE).floor() as u128 + 1)
+    }
+
+    pub fn get_default_threshold(&self, user: AccountId) -> Option<String> {
+        self.user_defaults.get(&user)
+    }
+
+    pub fn get_allowed_pairs(&self) -> Vec<String> {
+        self.allowed_pairs.clone()
+    }
+
+    pub fn get_allowed_curve_types(&self) -> Vec<u8> {
+        self.allowed_curve_types.clone()
+    }
+
+    pub fn get_supported_chains(&self) -> Vec<u64> {
+        self.supported_chain_ids.clone()
+    }
+
+    /// Whitelisted funding tokens with their creation fees.
+    pub fn get_allowed_tokens(&self) -> Vec<(AccountId, U128)> {
+        self.allowed_token_ids
+            .iter()
+            .map(|token_id| {
+                let creation_fee = self.token_creation_fees.get(token_id).unwrap_or(U128(0));
+                (token_id.clone(), creation_fee)
+            })
+            .collect()
+    }
+
+    pub fn get_referrer_earnings(&self, referrer: AccountId) -> U128 {
+        self.referrer_earnings.get(&referrer).unwrap_or(U128(0))
+    }
+
+    pub fn get_treasury(&self) -> AccountId {
+        self.treasury.clone()
+    }
+
+    pub fn get_treasury_earnings(&self) -> U128 {
+        self.treasury_earnings
+    }
+
+    pub fn get_pair_min_threshold(&self, token_pair: String) -> Option<String> {
+        self.pair_min_threshold.get(&normalize_pair(&token_pair))
+    }
+
+    pub fn get_max_profit_threshold(&self) -> f64 {
+        self.max_profit_threshold
+    }
+
+    /// Whether the intent owner could execute the intent's remaining notional
+    /// at these prices right now, using the same checks as `execute_arbitrage`.
+    pub fn is_executable(&self, intent_id: String, near_price: String, eth_price: String) -> bool {
+        let Some(intent) = self.intents.get(&intent_id) else {
+            return false;
+        };
+        let remaining = intent.deposit.0 - intent.executed_amount.0;
+        self.check_execution(&intent, &intent.user, &near_price, &eth_price, remaining)
+            .is_ok()
+    }
+
+    /// Profit (yoctoNEAR) that executing the intent's remaining notional at
+    /// these prices would record, or `None` if the intent doesn't exist or
+    /// `execute_arbitrage` by its owner would be rejected. Read-only
+    /// counterpart of `execute_arbitrage`.
+    pub fn preview_execution(
+        &self,
+        intent_id: String,
+        near_price: String,
+        eth_price: String,
+    ) -> Option<U128> {
+        let intent = self.intents.get(&intent_id)?;
+        let remaining = intent.deposit.0 - intent.executed_amount.0;
+        let (near_price, eth_price) = self
+            .check_execution(&intent, &intent.user, &near_price, &eth_price, remaining)
+            .ok()?;
+        Some(U128(to_yocto(execution_profit(
+            &intent,
+            near_price,
+            eth_price,
+            remaining,
+            self.profit_factor_bps,
+        ))))
+    }
+
+    pub fn get_claimable_profit(&self, user: AccountId, token_id: AccountId) -> U128 {
+        self.claimable_profits.get(&(user, token_id)).unwrap_or(U128(0))
+    }
+
+    /// Up to `limit` (at most 100) of the latest executions across all users,
+    /// newest first.
+    pub fn get_recent_executions(&self, limit: u64) -> Vec<ArbitrageExecution> {
+        let total = self.all_execution_ids.len();
+        let limit = limit.min(100).min(total);
+        (total - limit..total)
+            .rev()
+            .filter_map(|index| self.all_execution_ids.get(index))
+            .filter_map(|execution_id| self.executions.get(&execution_id))
+            .collect()
+    }
+
+    /// Intents whose `created_at` lies in `[start, end]`, scanning at most
+    /// `limit` (capped at 100) entries of the global intent list from
+    /// `from_index`. Page by advancing `from_index` by `limit` until it
+    /// reaches `get_total_intent_count`.
+    pub fn get_intents_created_between(
+        &self,
+        start: U64,
+        end: U64,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<ArbitrageIntent> {
+        let to_index = from_index.saturating_add(limit.min(100)).min(self.all_intent_ids.len());
+        (from_index..to_index)
+            .filter_map(|index| self.all_intent_ids.get(index))
+            .filter_map(|intent_id| self.intents.get(&intent_id))
+            .filter(|intent| intent.created_at.0 >= start.0 && intent.created_at.0 <= end.0)
+            .collect()
+    }
+
+    /// Intent at `index` in creation order, so keepers can scan with a cursor.
+    pub fn get_intent_by_index(&self, index: u64) -> Option<ArbitrageIntent> {
+        self.all_intent_ids
+            .get(index)
+            .and_then(|intent_id| self.intents.get(&intent_id))
+    }
+
+    pub fn get_total_intent_count(&self) -> u64 {
+        self.all_intent_ids.len()
+    }
+
+    /// Full intent records in creation order, at most `limit` (capped at
+    /// 100) from `from_index`, for bootstrapping indexers. Iterate until
+    /// `from_index` reaches `get_dump_cursors().total_intents`.
+    pub fn dump_intents(&self, from_index: u64, limit: u64) -> Vec<ArbitrageIntent> {
+        let to_index = from_index.saturating_add(limit.min(100)).min(self.all_intent_ids.len());
+        (from_index..to_index)
+            .filter_map(|index| self.all_intent_ids.get(index))
+            .filter_map(|intent_id| self.intents.get(&intent_id))
+            .collect()
+    }
+
+    /// Full execution records in execution order, paged like `dump_intents`.
+    pub fn dump_executions(&self, from_index: u64, limit: u64) -> Vec<ArbitrageExecution> {
+        let to_index =
+            from_index.saturating_add(limit.min(100)).min(self.all_execution_ids.len());
+        (from_index..to_index)
+            .filter_map(|index| self.all_execution_ids.get(index))
+            .filter_map(|execution_id| self.executions.get(&execution_id))
+            .collect()
+    }
+
+    pub fn get_dump_cursors(&self) -> DumpCursors {
+        DumpCursors {
+            total_intents: self.all_intent_ids.len(),
+            total_executions: self.all_execution_ids.len(),
+        }
+    }
+
+    pub fn get_intent_status(&self, intent_id: String) -> Option<String> {
+        self.intents
+            .get(&intent_id)
+            .map(|intent| intent.status.as_str().to_string())
+    }
+
+    /// Like `get_intent_status`, but tells cancelled ("cancelled") and merged
+    /// ("merged") intents apart from ids that never existed ("not_found").
+    pub fn get_intent_status_detailed(&self, intent_id: String) -> String {
+        if let Some(intent) = self.intents.get(&intent_id) {
+            intent.status.as_str().to_string()
+        } else if self.cancelled_intents.contains_key(&intent_id) {
+            "cancelled".to_string()
+        } else if self.merged_intents.contains_key(&intent_id) {
+            "merged".to_string()
+        } else {
+            "not_found".to_string()
+        }
+    }
+
+    pub fn get_intent(&self, intent_id: String) -> Option<ArbitrageIntent> {
+        self.intents.get(&intent_id)
+    }
+
+    /// Whether `keeper` may execute the intent: its owner, or one of its
+    /// authorized keepers unless `owner_only_execution` is set. False for
+    /// unknown intents.
+    pub fn is_authorized_keeper(&self, intent_id: String, keeper: AccountId) -> bool {
+        self.intents
+            .get(&intent_id)
+            .is_some_and(|intent| intent.can_execute(&keeper))
+    }
+
+    pub fn get_execution(&self, execution_id: String) -> Option<ArbitrageExecution> {
+        self.executions.get(&execution_id)
+    }
+
+    pub fn get_execution_net(&self, execution_id: String) -> Option<ExecutionNetView> {
+        let execution = self.executions.get(&execution_id)?;
+        let profit = to_yocto(execution.profit);
+        let fee = profit * self.fee_basis_points as u128 / 10_000;
+        let net_profit = profit
+            .saturating_sub(to_yocto(execution.gas_fees))
+            .saturating_sub(fee);
+        Some(ExecutionNetView { execution, fee: U128(fee), net_profit: U128(net_profit) })
+    }
+
+    pub fn get_cross_chain_signature(
+        &self,
+        execution_id: String,
+    ) -> Option<CrossChainSignatureView> {
+        self.cross_chain_signatures
+            .get(&execution_id)
+            .map(|signature| CrossChainSignatureView {
+                public_key: String::from(&signature.public_key),
+                signature: signature.signature,
+                chain_id: signature.chain_id,
+                nonce: signature.nonce,
+            })
+    }
+
+    pub fn get_global_stats(&self) -> GlobalStats {
+        GlobalStats {
+            total_intents: self.next_intent_id - 1,
+            total_executions: self.settled_executions,
+            total_profit_paid: self.total_profit_paid,
+            total_volume: self.total_volume,
+        }
+    }
+
+    pub fn get_balance_breakdown(&self) -> BalanceBreakdown {
+        let total = env::account_balance().as_yoctonear();
+        let storage_reserved =
+            env::storage_usage() as u128 * env::storage_byte_cost().as_yoctonear();
+        BalanceBreakdown {
+            total: U128(total),
+            reserved_profits: self.reserved_profits,
+            treasury_earnings: self.treasury_earnings,
+            intent_deposits: self.intent_deposits,
+            storage_reserved: U128(storage_reserved),
+            free: U128(self.free_balance()),
+        }
+    }
+
+    /// Storage layout version currently live, for confirming a migration ran.
+    pub fn get_state_version(&self) -> u32 {
+        self.state_version
+    }
+
+    pub fn get_fee_config(&self) -> FeeConfig {
+        FeeConfig {
+            fee_basis_points: self.fee_basis_points,
+            referrer_bps: self.referrer_bps,
+            keeper_fee_bps: self.default_keeper_fee_bps,
+            cancellation_fee_bps: self.cancellation_fee_bps,
+            creation_fee: self.creation_fee,
+            treasury: self.treasury.clone(),
+        }
+    }
+
+    pub fn get_contract_info(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": "ArbitrageAI Cross-Chain Agent",
+            "version": format!("{}.0.0", STATE_VERSION),
+            "owner": self.owner,
+            "total_intents": self.next_intent_id - 1,
+            "total_executions": self.settled_executions,
+            "paused": self.paused,
+            "fee_basis_points": self.fee_basis_points,
+            "oracle_configured": !self.oracle_accounts.is_empty(),
+            "dex_configured": self.dex_contract.is_some()
+        })
+    }
+}
+
+// Cross-Chain Integration Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::mock::MockAction;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, IntoStorageKey, NearToken, PromiseResult};
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(0))
+            .signer_account_id(predecessor_account_id.clone())
+            .predecessor_account_id(predecessor_account_id)
+            .attached_deposit(NearToken::from_near(1).as_yoctonear());
+        builder
+    }
+
+    fn create(
+        contract: &mut ArbitrageContract,
+        token_pair: &str,
+        min_profit_threshold: &str,
+    ) -> String {
+        contract.create_intent(
+            token_pair.to_string(),
+            Some(min_profit_threshold.to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn create_with_min_price_diff(
+        contract: &mut ArbitrageContract,
+        min_profit_threshold: &str,
+        min_price_diff: &str,
+    ) -> String {
+        contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some(min_profit_threshold.to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(min_price_diff.to_string()),
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn execute(
+        contract: &mut ArbitrageContract,
+        intent_id: &str,
+        near_price: &str,
+        eth_price: &str,
+        amount: U128,
+    ) {
+        let _ = contract.execute_arbitrage(
+            intent_id.to_string(),
+            near_price.to_string(),
+            eth_price.to_string(),
+            U64(env::block_timestamp()),
+            amount,
+            None,
+        );
+        let _ = settle_filled(contract);
+    }
+
+    /// Whitelists `token_id` for `ft_on_transfer` without a creation fee,
+    /// skipping the owner check.
+    fn allow_token(contract: &mut ArbitrageContract, token_id: AccountId) {
+        contract.token_creation_fees.insert(&token_id, &U128(0));
+        contract.allowed_token_ids.push(token_id);
+    }
+
+    /// Resolves the swap callback of the most recent execution, as the
+    /// runtime would once the swap promise completes.
+    fn settle_latest(
+        contract: &mut ArbitrageContract,
+        swap_result: Result<Option<u128>, PromiseError>,
+    ) -> PromiseOrValue<bool> {
+        let index = contract.all_execution_ids.len() - 1;
+        let execution_id = contract.all_execution_ids.get(index).unwrap();
+        contract.settle_swap(execution_id, env::predecessor_account_id(), None, swap_result)
+    }
+
+    /// Settles the most recent execution as a DEX filling it at the quoted
+    /// prices would: the notional comes back with the simulated profit.
+    fn settle_filled(contract: &mut ArbitrageContract) -> PromiseOrValue<bool> {
+        let index = contract.all_execution_ids.len() - 1;
+        let execution_id = contract.all_execution_ids.get(index).unwrap();
+        let execution = contract.executions.get(&execution_id).unwrap();
+        settle_latest(contract, Ok(Some(execution.amount.0 + to_yocto(execution.profit))))
+    }
+
+    #[test]
+    fn test_create_intent() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        assert_eq!(intent_id, "1");
+        let intent = contract.get_intent(intent_id).unwrap();
+        assert_eq!(intent.user, accounts(1));
+        assert_eq!(intent.token_pair, "ETH/USDC");
+        assert_eq!(intent.min_profit_threshold, 1.0);
+    }
+
+    #[test]
+    fn test_execute_arbitrage() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        context.attached_deposit(NearToken::from_near(0.1).as_yoctonear());
+        testing_env!(context.build());
+
+        let promise = contract.execute_arbitrage(
+            intent_id,
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(NearToken::from_near(1).as_yoctonear()),
+            None,
+        );
+        assert!(promise.is_valid());
+
+        let executions = contract.get_execution_history(accounts(1));
+        assert_eq!(executions.len(), 1);
+        assert_eq!(executions[0].token_pair, "ETH/USDC");
+        assert!(executions[0].profit > 0.0);
+    }
+
+    #[test]
+    fn test_state_version_after_init() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let contract = ArbitrageContract::new(accounts(0));
+        assert_eq!(contract.get_state_version(), STATE_VERSION);
+    }
+
+    /// Writes `value` under `key` of the map stored at `prefix`, as a
+    /// `LookupMap` would.
+    fn write_raw_entry<K: BorshSerialize, V: BorshSerialize>(prefix: &[u8], key: &K, value: &V) {
+        let mut storage_key = prefix.to_vec();
+        storage_key.extend(borsh::to_vec(key).unwrap());
+        env::storage_write(&storage_key, &borsh::to_vec(value).unwrap());
+    }
+
+    #[test]
+    fn test_migrate_from_old_state() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut old = OldArbitrageContract {
+            owner: accounts(0),
+            intents: LookupMap::new(b"intents".to_vec()),
+            user_intents: LookupMap::new(b"user_intents".to_vec()),
+            executions: LookupMap::new(b"executions".to_vec()),
+            user_executions: LookupMap::new(b"user_executions".to_vec()),
+            user_profits: LookupMap::new(b"user_profits".to_vec()),
+            next_intent_id: 3,
+            next_execution_id: 2,
+            cross_chain_signatures: LookupMap::new(b"cross_chain_sigs".to_vec()),
+        };
+        old.user_profits.insert(&accounts(1), &U128(42));
+        env::state_write(&old);
+
+        // Records exactly as v1.0.0 encoded them, independent of the Old* types:
+        // (id, user, token_pair, min_profit_threshold, status tag, created_at)
+        write_raw_entry(
+            b"intents",
+            &"1".to_string(),
+            &("1".to_string(), accounts(1), "ETH/USDC".to_string(), 1.0f64, 2u8, 5u64),
+        );
+        write_raw_entry(
+            b"intents",
+            &"2".to_string(),
+            &("2".to_string(), accounts(1), "NEAR/USDT".to_string(), 0.5f64, 0u8, 6u64),
+        );
+        // (id, intent_id, user, token_pair, price_diff, profit, gas_fees,
+        //  tx_hash, timestamp, near_price, eth_price)
+        write_raw_entry(
+            b"executions",
+            &"1".to_string(),
+            &(
+                "1".to_string(),
+                "1".to_string(),
+                accounts(1),
+                "ETH/USDC".to_string(),
+                50.0f64,
+                40.0f64,
+                0.01f64,
+                "ab".to_string(),
+                7u64,
+                3000.0f64,
+                2950.0f64,
+            ),
+        );
+
+        let mut contract = ArbitrageContract::migrate();
+        assert!(contract.paused);
+
+        // One record per page: two intents, then the execution
+        testing_env!(get_context(accounts(0)).build());
+        assert!(contract.migrate_records(1));
+        assert!(contract.migrate_records(1));
+        assert!(contract.paused);
+        assert!(!contract.migrate_records(1));
+        assert!(!contract.paused);
+        assert!(contract.migration_cursor.is_none());
+
+        assert_eq!(contract.get_state_version(), STATE_VERSION);
+        assert_eq!(contract.owner, accounts(0));
+        assert_eq!(contract.next_intent_id, 3);
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), U128(42));
+        assert_eq!(contract.get_total_intent_count(), 2);
+        assert_eq!(contract.get_intent_by_index(0).unwrap().id, "1");
+
+        let executed = contract.get_intent("1".to_string()).unwrap();
+        assert_eq!(executed.token_pair, "ETH/USDC");
+        assert_eq!(executed.status.as_str(), "executed");
+        assert_eq!(executed.created_at, U64(5));
+        assert_eq!(executed.executed_amount, executed.deposit);
+        assert!(executed.signer_pk.is_none());
+
+        let active = contract.get_intent("2".to_string()).unwrap();
+        assert_eq!(active.min_profit_threshold, 0.5);
+        assert_eq!(active.deposit, U128(V1_MIN_DEPOSIT));
+        assert_eq!(active.executed_amount, U128(0));
+
+        let execution = contract.get_execution("1".to_string()).unwrap();
+        assert_eq!(execution.profit, 40.0);
+        assert_eq!(execution.amount, U128(V1_MIN_DEPOSIT));
+        assert_eq!(contract.get_dump_cursors().total_executions, 1);
+        assert_eq!(contract.get_executions_for_intent("1".to_string()).len(), 1);
+        assert_eq!(contract.get_global_stats().total_volume, U128(V1_MIN_DEPOSIT));
+        let deposited = contract.total_deposited.get(&accounts(1));
+        assert_eq!(deposited, Some(U128(2 * V1_MIN_DEPOSIT)));
+        assert_eq!(contract.intent_deposits, U128(V1_MIN_DEPOSIT));
+
+        // A migrated active intent executes like any other
+        testing_env!(get_context(accounts(1)).build());
+        execute(&mut contract, "2", "3000.0", "2950.0", U128(V1_MIN_DEPOSIT));
+        assert_eq!(contract.get_intent("2".to_string()).unwrap().status.as_str(), "executed");
+    }
+
+    #[test]
+    #[should_panic(expected = "Intent 1 already exists")]
+    fn test_create_intent_rejects_existing_id() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        create(&mut contract, "ETH/USDC", "1.0");
+        // Simulate a migration that reset the counter
+        contract.next_intent_id = 1;
+        create(&mut contract, "NEAR/USDT", "1.0");
+    }
+
+    #[test]
+    #[should_panic(expected = "id counter overflow")]
+    fn test_intent_id_counter_overflow() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.next_intent_id = u64::MAX;
+        create(&mut contract, "ETH/USDC", "1.0");
+    }
+
+    #[test]
+    #[should_panic(expected = "id counter overflow")]
+    fn test_execution_id_counter_overflow() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.next_execution_id = u64::MAX;
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+    }
+
+    #[test]
+    fn test_get_intents_created_between() {
+        let mut context = get_context(accounts(1));
+        let mut contract = ArbitrageContract::new(accounts(0));
+        for timestamp in [100, 200, 300, 400] {
+            testing_env!(context.block_timestamp(timestamp).build());
+            create(&mut contract, "ETH/USDC", "1.0");
+        }
+
+        let created_at = |intents: Vec<ArbitrageIntent>| -> Vec<u64> {
+            intents.iter().map(|intent| intent.created_at.0).collect()
+        };
+        let window = contract.get_intents_created_between(U64(200), U64(300), 0, 10);
+        assert_eq!(created_at(window), vec![200, 300]);
+        let window = contract.get_intents_created_between(U64(201), U64(399), 0, 10);
+        assert_eq!(created_at(window), vec![300]);
+
+        let first_page = contract.get_intents_created_between(U64(0), U64(u64::MAX), 0, 3);
+        let second_page = contract.get_intents_created_between(U64(0), U64(u64::MAX), 3, 3);
+        assert_eq!(created_at(first_page), vec![100, 200, 300]);
+        assert_eq!(created_at(second_page), vec![400]);
+        assert!(contract.get_intents_created_between(U64(0), U64(500), 4, 3).is_empty());
+    }
+
+    #[test]
+    fn test_get_intent_by_index() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        assert_eq!(contract.get_total_intent_count(), 0);
+        let first = create(&mut contract, "ETH/USDC", "1.0");
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let second = create(&mut contract, "NEAR/USDC", "1.0");
+
+        assert_eq!(contract.get_total_intent_count(), 2);
+        assert_eq!(contract.get_intent_by_index(0).unwrap().id, first);
+        assert_eq!(contract.get_intent_by_index(1).unwrap().id, second);
+        assert!(contract.get_intent_by_index(2).is_none());
+    }
+
+    #[test]
+    fn test_ft_on_transfer_creates_token_funded_intent() {
+        // The token contract is the predecessor of ft_on_transfer.
+        let context = get_context(accounts(3));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        allow_token(&mut contract, accounts(3));
+        let unused = contract.ft_on_transfer(
+            accounts(1),
+            U128(500),
+            r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.5"}"#.to_string(),
+        );
+
+        assert_eq!(unused, U128(0));
+        let intent = contract.get_intent("1".to_string()).unwrap();
+        assert_eq!(intent.user, accounts(1));
+        assert_eq!(intent.token_pair, "ETH/USDC");
+        assert_eq!(intent.min_profit_threshold, 1.5);
+        assert_eq!(intent.funding_token, Some(accounts(3)));
+        assert_eq!(intent.deposit, U128(500));
+        assert_eq!(contract.get_user_intents(accounts(1)).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not whitelisted")]
+    fn test_ft_on_transfer_rejects_unlisted_token() {
+        let context = get_context(accounts(3));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.ft_on_transfer(
+            accounts(1),
+            U128(500),
+            r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.5"}"#.to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid msg")]
+    fn test_ft_on_transfer_rejects_malformed_msg() {
+        let context = get_context(accounts(3));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        allow_token(&mut contract, accounts(3));
+        contract.ft_on_transfer(accounts(1), U128(500), "ETH/USDC".to_string());
+    }
+
+    #[test]
+    fn test_token_funded_intents_skip_near_deposit_accounting() {
+        let mut context = get_context(accounts(3));
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        allow_token(&mut contract, accounts(3));
+        contract.ft_on_transfer(
+            accounts(1),
+            U128(500),
+            r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.5"}"#.to_string(),
+        );
+
+        // No NEAR storage deposit is taken, and token units stay out of the
+        // NEAR totals
+        assert_eq!(contract.get_intent("1".to_string()).unwrap().deposit, U128(500));
+        assert_eq!(contract.total_deposited.get(&accounts(1)), None);
+        assert_eq!(contract.intent_deposits, U128(0));
+    }
+
+    #[test]
+    fn test_ft_on_transfer_charges_token_creation_fee() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_allowed_token(accounts(3), U128(20));
+        assert_eq!(contract.get_allowed_tokens(), vec![(accounts(3), U128(20))]);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let unused = contract.ft_on_transfer(
+            accounts(1),
+            U128(500),
+            r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.5"}"#.to_string(),
+        );
+
+        assert_eq!(unused, U128(0));
+        let intent = contract.get_intent("1".to_string()).unwrap();
+        assert_eq!(intent.deposit, U128(480));
+        // Token fees are forwarded, never booked against NEAR earnings
+        assert_eq!(contract.treasury_earnings, U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "is not whitelisted")]
+    fn test_removed_token_can_no_longer_fund_intents() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_allowed_token(accounts(3), U128(0));
+        contract.remove_allowed_token(accounts(3));
+        assert!(contract.get_allowed_tokens().is_empty());
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.ft_on_transfer(
+            accounts(1),
+            U128(500),
+            r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.5"}"#.to_string(),
+        );
+    }
+
+    #[test]
+    fn test_partial_executions_sum_to_full_execution() {
+        let mut context = get_context(accounts(2));
+        testing_env!(context.build());
+        let full = U128(NearToken::from_near(1).as_yoctonear());
+        let half = U128(full.0 / 2);
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let full_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &full_id, "3000.0", "2950.0", full);
+        assert!(matches!(contract.get_intent(full_id).unwrap().status, IntentStatus::Executed));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let tranche_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &tranche_id, "3000.0", "2950.0", half);
+        let intent = contract.get_intent(tranche_id.clone()).unwrap();
+        assert!(matches!(intent.status, IntentStatus::Active));
+        assert_eq!(intent.executed_amount, half);
+
+        execute(&mut contract, &tranche_id, "3000.0", "2950.0", half);
+        assert!(matches!(contract.get_intent(tranche_id).unwrap().status, IntentStatus::Executed));
+
+        let executions = contract.get_execution_history(accounts(1));
+        assert_eq!(executions.len(), 2);
+        assert_eq!(executions[0].amount, half);
+        assert_eq!(
+            contract.get_total_profit(accounts(1), accounts(0)),
+            contract.get_total_profit(accounts(2), accounts(0))
+        );
+    }
+
+    #[test]
+    fn test_execution_ids_are_unique_hashes() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let tranche = U128(NearToken::from_millinear(250).as_yoctonear());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        // Two executions in the same block still get distinct ids via the nonce
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", tranche);
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", tranche);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let other_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &other_id, "3000.0", "2950.0", tranche);
+
+        let mut ids: Vec<String> = contract
+            .get_execution_history(accounts(1))
+            .into_iter()
+            .chain(contract.get_execution_history(accounts(2)))
+            .map(|execution| execution.id)
+            .collect();
+        assert_eq!(ids.len(), 3);
+        for id in &ids {
+            assert_eq!(id.len(), 64);
+            assert!(id.parse::<u64>().is_err(), "id {} looks sequential", id);
+            assert!(contract.get_execution(id.clone()).is_some());
+        }
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+    }
+
+    #[test]
+    fn test_get_executions_for_intent() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let tranche = U128(NearToken::from_millinear(250).as_yoctonear());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let other_id = create(&mut contract, "NEAR/USDC", "1.0");
+        // The large spreads pay out more than the default mocked balance
+        testing_env!(context.account_balance(NearToken::from_near(1_000)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", tranche);
+        execute(&mut contract, &other_id, "3000.0", "2950.0", tranche);
+        execute(&mut contract, &intent_id, "3100.0", "2950.0", tranche);
+        execute(&mut contract, &intent_id, "3200.0", "2950.0", tranche);
+
+        let executions = contract.get_executions_for_intent(intent_id.clone());
+        assert_eq!(executions.len(), 3);
+        assert!(executions.iter().all(|execution| execution.intent_id == intent_id));
+        let near_prices: Vec<f64> = executions.iter().map(|execution| execution.near_price).collect();
+        assert_eq!(near_prices, vec![3000.0, 3100.0, 3200.0]);
+        assert!(contract.get_executions_for_intent("99".to_string()).is_empty());
+    }
+
+    #[test]
+    fn test_preview_execution_matches_recorded_profit() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let full = U128(NearToken::from_near(1).as_yoctonear());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.5");
+
+        // 1% spread is under the 1.5% threshold
+        let below = contract.preview_execution(
+            intent_id.clone(),
+            "3030.0".to_string(),
+            "3000.0".to_string(),
+        );
+        assert_eq!(below, None);
+
+        let preview = contract
+            .preview_execution(intent_id.clone(), "3000.0".to_string(), "2950.0".to_string())
+            .unwrap();
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), U128(0));
+
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", full);
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), preview);
+        assert_eq!(
+            contract.preview_execution(intent_id, "3000.0".to_string(), "2950.0".to_string()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_preview_execution_rejects_what_execute_would() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(1_000).build());
+        let half = U128(NearToken::from_millinear(500).as_yoctonear());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let preview = |contract: &ArbitrageContract, near_price: &str, eth_price: &str| {
+            contract.preview_execution(
+                intent_id.clone(),
+                near_price.to_string(),
+                eth_price.to_string(),
+            )
+        };
+
+        let (near_price, eth_price) = ("3000.0".to_string(), "2950.0".to_string());
+        assert_eq!(contract.preview_execution("99".to_string(), near_price, eth_price), None);
+        assert_eq!(preview(&contract, "not a price", "2950.0"), None);
+        assert_eq!(preview(&contract, "3000.0", "0"), None);
+        assert!(preview(&contract, "3000.0", "2950.0").is_some());
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_execution_cooldown(U64(1_000));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", half);
+        assert_eq!(preview(&contract, "3000.0", "2950.0"), None);
+
+        testing_env!(context.block_timestamp(2_000).build());
+        assert!(preview(&contract, "3000.0", "2950.0").is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount must be greater than zero")]
+    fn test_execute_rejects_zero_amount() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(0));
+    }
+
+    #[test]
+    fn test_execution_cooldown() {
+        let mut context = get_context(accounts(0));
+        context.block_timestamp(1_000);
+        testing_env!(context.build());
+        let tranche = U128(NearToken::from_millinear(250).as_yoctonear());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_execution_cooldown(U64(60));
+        assert_eq!(contract.get_execution_cooldown(), U64(60));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", tranche);
+        assert_eq!(contract.get_intent(intent_id.clone()).unwrap().last_executed_at, U64(1_000));
+
+        // Exactly one cooldown later the intent is executable again
+        testing_env!(context.block_timestamp(1_060).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", tranche);
+        assert_eq!(contract.get_executions_for_intent(intent_id).len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cooldown active")]
+    fn test_execution_within_cooldown_panics() {
+        let mut context = get_context(accounts(0));
+        context.block_timestamp(1_000);
+        testing_env!(context.build());
+        let tranche = U128(NearToken::from_millinear(250).as_yoctonear());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_execution_cooldown(U64(60));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", tranche);
+
+        testing_env!(context.block_timestamp(1_059).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", tranche);
+    }
+
+    #[test]
+    fn test_intent_status_serializes_as_lowercase_string() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        let json = serde_json::to_value(contract.get_intent(intent_id.clone()).unwrap()).unwrap();
+        assert_eq!(json["status"], "active");
+        assert_eq!(contract.get_intent_status(intent_id.clone()), Some("active".to_string()));
+
+        contract.pause_intent(intent_id.clone());
+        assert_eq!(contract.get_intent_status(intent_id), Some("paused".to_string()));
+        assert_eq!(
+            serde_json::to_string(&IntentStatus::Executed).unwrap(),
+            "\"executed\""
+        );
+        assert_eq!(contract.get_intent_status("99".to_string()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Profit below minimum absolute profit")]
+    fn test_execute_rejects_profit_below_absolute_minimum() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            Some(U128(NearToken::from_near(50).as_yoctonear())),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        // 1.7% spread clears the percentage threshold but only earns 40 NEAR
+        let full = U128(NearToken::from_near(1).as_yoctonear());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", full);
+    }
+
+    #[test]
+    #[should_panic(expected = "Profit below threshold")]
+    fn test_execute_rejects_percentage_below_threshold_with_absolute_minimum_met() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.5".to_string()),
+            None,
+            Some(U128(1)),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        // 1% spread pays far more than 1 yoctoNEAR but misses the 1.5% threshold
+        let full = U128(NearToken::from_near(1).as_yoctonear());
+        execute(&mut contract, &intent_id, "3030.0", "3000.0", full);
+    }
+
+    #[test]
+    fn test_execute_with_both_thresholds_met() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            Some(U128(NearToken::from_near(39).as_yoctonear())),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(contract
+            .preview_execution(intent_id.clone(), "3000.0".to_string(), "2950.0".to_string())
+            .is_some());
+        let full = U128(NearToken::from_near(1).as_yoctonear());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", full);
+        assert_eq!(contract.get_execution_history(accounts(1)).len(), 1);
+    }
+
+    #[test]
+    fn test_intent_labels() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            Some("weekend scalp".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        let intents = contract.get_user_intents(accounts(1));
+        assert_eq!(intents[0].label.as_deref(), Some("weekend scalp"));
+
+        contract.set_intent_label(intent_id.clone(), Some("core position".to_string()));
+        assert_eq!(
+            contract.get_intent(intent_id.clone()).unwrap().label.as_deref(),
+            Some("core position")
+        );
+
+        contract.set_intent_label(intent_id.clone(), None);
+        assert_eq!(contract.get_intent(intent_id).unwrap().label, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Label exceeds 64 bytes")]
+    fn test_intent_label_length_limit() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.set_intent_label(intent_id, Some("x".repeat(65)));
+    }
+
+    #[test]
+    fn test_authorized_keeper_executes_for_owner() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.add_keeper(intent_id.clone(), accounts(2));
+        contract.set_owner_only_execution(intent_id.clone(), false);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+
+        assert_eq!(contract.get_execution_history(accounts(1)).len(), 1);
+        assert!(contract.get_total_profit(accounts(1), accounts(0)).0 > 0);
+        assert!(contract.get_execution_history(accounts(2)).is_empty());
+        assert_eq!(contract.get_total_profit(accounts(2), accounts(0)), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only intent owner or an authorized keeper can execute")]
+    fn test_owner_only_execution_rejects_keeper() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.add_keeper(intent_id.clone(), accounts(2));
+        assert!(contract.get_intent(intent_id.clone()).unwrap().owner_only_execution);
+        assert!(!contract.is_authorized_keeper(intent_id.clone(), accounts(2)));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+    }
+
+    #[test]
+    fn test_owner_only_execution_toggle() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.add_keeper(intent_id.clone(), accounts(2));
+        contract.set_owner_only_execution(intent_id.clone(), false);
+        assert!(contract.is_authorized_keeper(intent_id.clone(), accounts(2)));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_execution_history(accounts(1)).len(), 1);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_owner_only_execution(intent_id.clone(), true);
+        assert!(!contract.is_authorized_keeper(intent_id.clone(), accounts(2)));
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_execution_history(accounts(1)).len(), 2);
+    }
+
+    #[test]
+    fn test_profit_at_threshold_executes_unless_strict() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        assert_eq!(profit_percentage(2020.0, 2000.0), 1.0);
+        let executable = |contract: &ArbitrageContract, near_price: &str| {
+            contract.is_executable(intent_id.clone(), near_price.to_string(), "2000.0".to_string())
+        };
+
+        assert!(executable(&contract, "2020.0"));
+        execute(&mut contract, &intent_id, "2020.0", "2000.0", U128(1_000));
+        assert_eq!(contract.get_execution_history(accounts(1)).len(), 1);
+
+        contract.set_strict_threshold(intent_id.clone(), true);
+        assert!(!executable(&contract, "2020.0"));
+        assert!(executable(&contract, "2021.0"));
+        assert!(!executable(&contract, "2019.0"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Profit not above threshold")]
+    fn test_strict_threshold_rejects_profit_at_threshold() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.set_strict_threshold(intent_id.clone(), true);
+        execute(&mut contract, &intent_id, "2020.0", "2000.0", U128(1_000));
+    }
+
+    #[test]
+    fn test_reset_profit_counter_keeps_epochs() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        let first_profit = contract.get_total_profit(accounts(1), accounts(0));
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        assert_eq!(contract.reset_profit_counter(accounts(1)), 0);
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), U128(0));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(2_000));
+        let second_profit = contract.get_total_profit(accounts(1), accounts(0));
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        assert_eq!(contract.reset_profit_counter(accounts(1)), 1);
+
+        assert_eq!(contract.get_profit_for_epoch(accounts(1), 0), Some(first_profit));
+        assert_eq!(contract.get_profit_for_epoch(accounts(1), 1), Some(second_profit));
+        assert_ne!(first_profit, second_profit);
+        assert_eq!(contract.get_profit_for_epoch(accounts(1), 2), None);
+        assert_eq!(contract.total_profit_paid.0, first_profit.0 + second_profit.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only contract owner or Admin role can call this method")]
+    fn test_reset_profit_counter_requires_admin() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.reset_profit_counter(accounts(1));
+    }
+
+    #[test]
+    fn test_profit_factor_scales_profit() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_profit_factor_bps(5_000);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let deposit = U128(NearToken::from_near(1).as_yoctonear());
+        execute(&mut contract, &intent_id, "3.0", "2.5", deposit);
+        assert_eq!(contract.get_executions_for_intent(intent_id)[0].profit, 0.25);
+    }
+
+    #[test]
+    #[should_panic(expected = "Profit factor cannot exceed 100%")]
+    fn test_set_profit_factor_rejects_over_100_percent() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_profit_factor_bps(50_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Profit exceeds price difference")]
+    fn test_execute_rejects_profit_above_spread() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        // Only reachable through a bad migration, since the setter caps it
+        contract.profit_factor_bps = 15_000;
+
+        // Small enough a spread that the notional cap doesn't hide the excess
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let deposit = U128(NearToken::from_near(1).as_yoctonear());
+        execute(&mut contract, &intent_id, "3.0", "2.5", deposit);
+    }
+
+    #[test]
+    fn test_pair_volatility() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "0.01");
+        assert_eq!(contract.get_pair_volatility("ETH/USDC".to_string()), None);
+
+        // Diffs 4, 6, 2, 8: mean 5, population variance (1 + 1 + 9 + 9) / 4 = 5
+        for eth_price in ["2996.0", "2994.0", "2998.0", "2992.0"] {
+            execute(&mut contract, &intent_id, "3000.0", eth_price, U128(1_000));
+        }
+        let stats = contract.pair_stats.get(&"ETH/USDC".to_string()).unwrap();
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.mean, 5_000_000);
+        assert_eq!(stats.m2 / stats.count as i128, 5_000_000_000_000);
+        // sqrt(5) = 2.236067...
+        let volatility = contract.get_pair_volatility("USDC/ETH".to_string());
+        assert_eq!(volatility, Some("2236067".to_string()));
+    }
+
+    #[test]
+    fn test_recompute_pair_stats() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let eth = create(&mut contract, "ETH/USDC", "0.01");
+        let btc = create(&mut contract, "BTC/USDC", "0.01");
+        for eth_price in ["2996.0", "2994.0", "2998.0"] {
+            execute(&mut contract, &eth, "3000.0", eth_price, U128(1_000));
+            execute(&mut contract, &btc, "3000.0", "2900.0", U128(1_000));
+        }
+        let pair = "ETH/USDC".to_string();
+        let expected = contract.get_pair_volatility(pair.clone());
+
+        let mut stats = contract.pair_stats.get(&pair).unwrap();
+        stats.count = 1;
+        stats.m2 = 0;
+        contract.pair_stats.insert(&pair, &stats);
+        assert_ne!(contract.get_pair_volatility(pair.clone()), expected);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut cursor = Some(0);
+        let mut pages = 0;
+        while let Some(from_index) = cursor {
+            cursor = contract.recompute_pair_stats("USDC/ETH".to_string(), from_index, 4);
+            pages += 1;
+        }
+        assert_eq!(pages, 2);
+        assert_eq!(contract.pair_stats.get(&pair).unwrap().count, 3);
+        assert_eq!(contract.get_pair_volatility(pair), expected);
+    }
+
+    #[test]
+    fn test_recompute_pair_stats_for_unexecuted_pair() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let eth = create(&mut contract, "ETH/USDC", "0.01");
+        execute(&mut contract, &eth, "3000.0", "2996.0", U128(1_000));
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        assert_eq!(contract.recompute_pair_stats("BTC/USDC".to_string(), 0, 100), None);
+        assert!(contract.pair_stats.get(&"BTC/USDC".to_string()).is_none());
+        assert_eq!(contract.get_pair_volatility("BTC/USDC".to_string()), None);
+    }
+
+    #[test]
+    fn test_create_intents_from_template() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.create_template(
+            "scalp".to_string(),
+            "USDC/ETH".to_string(),
+            "1.5".to_string(),
+            Some(1),
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let first = contract.create_intent_from_template("scalp".to_string());
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let second = contract.create_intent_from_template("scalp".to_string());
+
+        for (intent_id, user) in [(&first, accounts(1)), (&second, accounts(2))] {
+            let intent = contract.get_intent(intent_id.clone()).unwrap();
+            assert_eq!(intent.user, user);
+            assert_eq!(intent.token_pair, "ETH/USDC");
+            assert_eq!(intent.min_profit_threshold, 1.5);
+            assert_eq!(intent.max_executions, Some(1));
+            assert_eq!(intent.deposit, U128(NearToken::from_near(1).as_yoctonear()));
+        }
+
+        // max_executions stops the intent after its first execution
+        execute(&mut contract, &second, "3000.0", "2950.0", U128(1_000));
+        assert!(!contract.is_executable(second, "3000.0".into(), "2950.0".into()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Template not found")]
+    fn test_create_intent_from_unknown_template() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.create_intent_from_template("missing".to_string());
+    }
+
+    #[test]
+    fn test_max_active_intents_per_user() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_active_intents_per_user(2);
+        assert_eq!(contract.get_max_active_intents_per_user(), 2);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let first = create(&mut contract, "ETH/USDC", "1.0");
+        create(&mut contract, "NEAR/USDT", "1.0");
+
+        // Executed and cancelled intents free their slot
+        let deposit = U128(NearToken::from_near(1).as_yoctonear());
+        execute(&mut contract, &first, "3000.0", "2950.0", deposit);
+        let third = create(&mut contract, "BTC/USDC", "1.0");
+        let _ = contract.cancel_intent(third);
+        create(&mut contract, "BTC/USDC", "1.0");
+        assert_eq!(contract.get_active_intents(accounts(1)).len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Active intent limit of 2 reached")]
+    fn test_paused_intents_count_towards_active_limit() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_active_intents_per_user(2);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        create(&mut contract, "ETH/USDC", "1.0");
+        let second = create(&mut contract, "NEAR/USDT", "1.0");
+        contract.pause_intent(second);
+        create(&mut contract, "BTC/USDC", "1.0");
+    }
+
+    #[test]
+    #[should_panic(expected = "Active intent limit of 1 reached")]
+    fn test_create_intent_rejects_over_active_limit() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_active_intents_per_user(1);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        create(&mut contract, "ETH/USDC", "1.0");
+        create(&mut contract, "NEAR/USDT", "1.0");
+    }
+
+    #[test]
+    fn test_cancel_intent_refunds_deposit() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        let _ = contract.cancel_intent(intent_id.clone());
+
+        assert!(contract.get_intent(intent_id).is_none());
+        assert!(contract.get_user_intents(accounts(1)).is_empty());
+        let expected = NearToken::from_yoctonear(NearToken::from_near(1).as_yoctonear() - 1_000);
+        assert!(get_created_receipts().iter().any(|receipt| {
+            receipt.receiver_id == accounts(1)
+                && receipt.actions.iter().any(|action| {
+                    matches!(action, MockAction::Transfer { deposit, .. } if *deposit == expected)
+                })
+        }));
+    }
+
+    #[test]
+    fn test_failed_cancel_refund_stays_claimable() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.cancel_intent(intent_id);
+        let deposit = NearToken::from_near(1).as_yoctonear();
+        assert_eq!(contract.intent_deposits, U128(0));
+
+        callback_context(&mut context, PromiseResult::Failed);
+        let refunded = contract.on_refund_resolved(accounts(1), accounts(0), U128(deposit));
+        assert_eq!(refunded, U128(0));
+        assert_eq!(contract.get_claimable_profit(accounts(1), accounts(0)), U128(deposit));
+        assert_eq!(contract.reserved_profits, U128(deposit));
+
+        callback_context(&mut context, PromiseResult::Successful(Vec::new()));
+        let refunded = contract.on_refund_resolved(accounts(1), accounts(0), U128(deposit));
+        assert_eq!(refunded, U128(deposit));
+        assert_eq!(contract.get_claimable_profit(accounts(1), accounts(0)), U128(deposit));
+    }
+
+    #[test]
+    #[should_panic(expected = "Intent has a swap in flight")]
+    fn test_cancel_intent_waits_for_pending_swap() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.execute_arbitrage(
+            intent_id.clone(),
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(1_000),
+            None,
+        );
+        let _ = contract.cancel_intent(intent_id);
+    }
+
+    #[test]
+    fn test_intent_status_detailed_distinguishes_cancelled() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+
+        let cancelled = create(&mut contract, "ETH/USDC", "1.0");
+        let active = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.cancel_intent(cancelled.clone());
+
+        assert!(contract.get_intent(cancelled.clone()).is_none());
+        assert_eq!(contract.get_intent_status_detailed(cancelled), "cancelled");
+        assert_eq!(contract.get_intent_status_detailed(active.clone()), "active");
+        contract.pause_intent(active.clone());
+        assert_eq!(contract.get_intent_status_detailed(active), "paused");
+        assert_eq!(contract.get_intent_status_detailed("99".to_string()), "not_found");
+    }
+
+    #[test]
+    fn test_cancellation_fee_goes_to_treasury() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_cancellation_fee_bps(1_000);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.cancel_intent(intent_id);
+
+        let deposit = NearToken::from_near(1).as_yoctonear();
+        assert_eq!(contract.get_treasury_earnings(), U128(deposit / 10));
+        let expected = NearToken::from_yoctonear(deposit - deposit / 10);
+        assert!(get_created_receipts().iter().any(|receipt| {
+            receipt.receiver_id == accounts(1)
+                && receipt.actions.iter().any(|action| {
+                    matches!(action, MockAction::Transfer { deposit, .. } if *deposit == expected)
+                })
+        }));
+    }
+
+    #[test]
+    fn test_creation_fee_retained_on_cancel() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let fee = NearToken::from_millinear(500).as_yoctonear();
+        contract.set_creation_fee(U128(fee));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(NearToken::from_millinear(1_500))
+            .build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let storage = NearToken::from_near(1).as_yoctonear();
+        assert_eq!(contract.get_intent(intent_id.clone()).unwrap().deposit, U128(storage));
+        assert_eq!(contract.get_treasury_earnings(), U128(fee));
+
+        let _ = contract.cancel_intent(intent_id);
+        assert_eq!(contract.get_treasury_earnings(), U128(fee));
+        let expected = NearToken::from_yoctonear(storage);
+        assert!(get_created_receipts().iter().any(|receipt| {
+            receipt.receiver_id == accounts(1)
+                && receipt.actions.iter().any(|action| {
+                    matches!(action, MockAction::Transfer { deposit, .. } if *deposit == expected)
+                })
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "including a creation fee of")]
+    fn test_create_intent_requires_creation_fee() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_creation_fee(U128(1));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        create(&mut contract, "ETH/USDC", "1.0");
+    }
+
+    #[test]
+    #[should_panic(expected = "Cancellation fee cannot exceed 20%")]
+    fn test_cancellation_fee_capped() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_cancellation_fee_bps(2_001);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only intent owner can cancel")]
+    fn test_cancel_intent_requires_owner() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let _ = contract.cancel_intent(intent_id);
+    }
+
+    #[test]
+    fn test_get_intents_by_public_key() {
+        let first_pk: PublicKey =
+            "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap();
+        let second_pk: PublicKey =
+            "ed25519:DcA2MzgpJbrUATQLLceocVckhhAqrkingax4oJ9kZ847".parse().unwrap();
+        let mut context = get_context(accounts(1));
+        testing_env!(context.signer_account_pk(first_pk.clone()).build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+
+        let first = create(&mut contract, "ETH/USDC", "1.0");
+        let second = create(&mut contract, "NEAR/USDT", "1.0");
+        // Same account, different access key
+        testing_env!(context.signer_account_pk(second_pk.clone()).build());
+        let third = create(&mut contract, "BTC/USDC", "1.0");
+
+        let ids = |pk: &PublicKey, from_index, limit| -> Vec<String> {
+            contract
+                .get_intents_by_public_key(pk.clone(), from_index, limit)
+                .into_iter()
+                .map(|intent| intent.id)
+                .collect()
+        };
+        assert_eq!(ids(&first_pk, 0, 10), vec![first, second.clone()]);
+        assert_eq!(ids(&first_pk, 1, 10), vec![second]);
+        assert_eq!(ids(&second_pk, 0, 10), vec![third.clone()]);
+        assert_eq!(contract.get_intent(third).unwrap().signer_pk, Some(second_pk));
+    }
+
+    #[test]
+    fn test_is_authorized_keeper() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.add_keeper(intent_id.clone(), accounts(2));
+        contract.set_owner_only_execution(intent_id.clone(), false);
+
+        assert!(contract.is_authorized_keeper(intent_id.clone(), accounts(2)));
+        assert!(contract.is_authorized_keeper(intent_id.clone(), accounts(1)));
+        assert!(!contract.is_authorized_keeper(intent_id, accounts(3)));
+        assert!(!contract.is_authorized_keeper("99".to_string(), accounts(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only intent owner or an authorized keeper can execute")]
+    fn test_unauthorized_keeper_cannot_execute() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.add_keeper(intent_id.clone(), accounts(2));
+        contract.set_owner_only_execution(intent_id.clone(), false);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only intent owner or an authorized keeper can execute")]
+    fn test_removed_keeper_cannot_execute() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.add_keeper(intent_id.clone(), accounts(2));
+        contract.set_owner_only_execution(intent_id.clone(), false);
+        contract.remove_keeper(intent_id.clone(), accounts(2));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+    }
+
+    #[test]
+    fn test_get_user_active_pairs() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        create(&mut contract, "ETH/USDC", "1.0");
+        create(&mut contract, "ETH/USDC", "2.0");
+        create(&mut contract, "NEAR/USDC", "1.0");
+        let paused = create(&mut contract, "BTC/USDC", "1.0");
+        contract.pause_intent(paused);
+
+        let mut pairs = contract.get_user_active_pairs(accounts(1));
+        pairs.sort();
+        assert_eq!(pairs, vec!["ETH/USDC".to_string(), "NEAR/USDC".to_string()]);
+        assert!(contract.get_user_active_pairs(accounts(2)).is_empty());
+    }
+
+    #[test]
+    fn test_keeper_fee_paid_to_keeper() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.add_keeper(intent_id.clone(), accounts(2));
+        contract.set_owner_only_execution(intent_id.clone(), false);
+        contract.set_keeper_fee_bps(intent_id.clone(), 1_000);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+
+        let gross = contract.total_profit_paid.0;
+        let keeper_fee = gross * 1_000 / 10_000;
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)).0, gross - keeper_fee);
+        assert!(get_logs()
+            .iter()
+            .any(|log| log.starts_with(&format!("Paid keeper fee {} ", keeper_fee))));
+    }
+
+    #[test]
+    fn test_self_execution_skips_keeper_fee() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.add_keeper(intent_id.clone(), accounts(2));
+        contract.set_owner_only_execution(intent_id.clone(), false);
+        contract.set_keeper_fee_bps(intent_id.clone(), 1_000);
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), contract.total_profit_paid);
+        assert!(!get_logs().iter().any(|log| log.starts_with("Paid keeper fee")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot pause an executed intent")]
+    fn test_pause_intent_rejects_executed_intent() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let full = U128(NearToken::from_near(1).as_yoctonear());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", full);
+        contract.pause_intent(intent_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot resume an executed intent")]
+    fn test_resume_intent_rejects_executed_intent() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let full = U128(NearToken::from_near(1).as_yoctonear());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", full);
+        contract.resume_intent(intent_id);
+    }
+
+    #[test]
+    fn test_resume_paused_intent_before_expiry() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(1_000).build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            None,
+            None,
+            Some(U64(5_000)),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.pause_intent(intent_id.clone());
+
+        testing_env!(context.block_timestamp(4_999).build());
+        contract.resume_intent(intent_id.clone());
+        assert!(matches!(contract.get_intent(intent_id).unwrap().status, IntentStatus::Active));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot resume expired intent")]
+    fn test_resume_expired_intent_rejected() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(1_000).build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            None,
+            None,
+            Some(U64(5_000)),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.pause_intent(intent_id.clone());
+
+        testing_env!(context.block_timestamp(5_000).build());
+        contract.resume_intent(intent_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Intent expired")]
+    fn test_execute_expired_intent_rejected() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(1_000).build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            None,
+            None,
+            Some(U64(5_000)),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(contract.get_active_intents(accounts(1)).len(), 1);
+
+        testing_env!(context.block_timestamp(6_000).build());
+        assert!(contract.get_active_intents(accounts(1)).is_empty());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+    }
+
+    #[test]
+    fn test_get_recent_executions() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        assert!(contract.get_recent_executions(10).is_empty());
+        let first = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &first, "3000.0", "2950.0", U128(1_000));
+        execute(&mut contract, &first, "3000.0", "2950.0", U128(2_000));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let second = create(&mut contract, "NEAR/USDT", "1.0");
+        execute(&mut contract, &second, "3000.0", "2950.0", U128(3_000));
+
+        let recent = contract.get_recent_executions(2);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].user, accounts(2));
+        assert_eq!(recent[0].amount, U128(3_000));
+        assert_eq!(recent[1].user, accounts(1));
+        assert_eq!(recent[1].amount, U128(2_000));
+
+        let all = contract.get_recent_executions(1_000);
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[2].amount, U128(1_000));
+    }
+
+    #[test]
+    fn test_first_execution_bonus_once_per_pair() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let bonus = NearToken::from_near(1).as_yoctonear();
+        contract.set_first_execution_bonus(U128(bonus));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let first = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &first, "3000.0", "2950.0", U128(1_000));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let repeat = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &repeat, "3000.0", "2950.0", U128(1_000));
+
+        let first_profit = contract.get_total_profit(accounts(1), accounts(0)).0;
+        let repeat_profit = contract.get_total_profit(accounts(2), accounts(0)).0;
+        assert_eq!(first_profit - repeat_profit, bonus);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let new_pair = create(&mut contract, "NEAR/USDT", "1.0");
+        execute(&mut contract, &new_pair, "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_total_profit(accounts(3), accounts(0)).0, first_profit);
+    }
+
+    #[test]
+    fn test_is_executable() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_execution_cooldown(U64(1_000));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(100).build());
+        let is_executable = |contract: &ArbitrageContract, intent_id: &str, eth_price: &str| {
+            let near_price = "3000.0".to_string();
+            contract.is_executable(intent_id.to_string(), near_price, eth_price.to_string())
+        };
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        assert!(is_executable(&contract, &intent_id, "2950.0"));
+
+        // Spread below threshold, unparseable and equal prices
+        assert!(!is_executable(&contract, &intent_id, "2990.0"));
+        assert!(!is_executable(&contract, &intent_id, "abc"));
+        assert!(!is_executable(&contract, &intent_id, "3000.0"));
+        assert!(!is_executable(&contract, "99", "2950.0"));
+
+        // Cooldown after a partial execution
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert!(!is_executable(&contract, &intent_id, "2950.0"));
+        testing_env!(context.block_timestamp(1_100).build());
+        assert!(is_executable(&contract, &intent_id, "2950.0"));
+
+        // Paused
+        contract.pause_intent(intent_id.clone());
+        assert!(!is_executable(&contract, &intent_id, "2950.0"));
+        contract.resume_intent(intent_id.clone());
+
+        // Not yet active, then expired
+        let scheduled = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            None,
+            Some(U64(2_000)),
+            Some(U64(3_000)),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(!is_executable(&contract, &scheduled, "2950.0"));
+        testing_env!(context.block_timestamp(2_000).build());
+        assert!(is_executable(&contract, &scheduled, "2950.0"));
+        testing_env!(context.block_timestamp(3_000).build());
+        assert!(!is_executable(&contract, &scheduled, "2950.0"));
+
+        // Fully executed
+        let full = U128(NearToken::from_near(1).as_yoctonear() - 1_000);
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", full);
+        assert!(!is_executable(&contract, &intent_id, "2950.0"));
+    }
+
+    #[test]
+    fn test_execute_with_fresh_price_timestamp() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_price_age(U64(60_000_000_000));
+
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(100_000_000_000).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.execute_arbitrage(
+            intent_id,
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(40_000_000_000),
+            U128(1_000),
+            None,
+        );
+        assert_eq!(contract.get_execution_history(accounts(1)).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Prices are stale")]
+    fn test_execute_rejects_stale_price_timestamp() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_price_age(U64(60_000_000_000));
+
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(100_000_000_000).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.execute_arbitrage(
+            intent_id,
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(39_999_999_999),
+            U128(1_000),
+            None,
+        );
+    }
+
+    fn batch_entry(intent_id: &str, near_price: &str, eth_price: &str) -> BatchExecution {
+        BatchExecution {
+            intent_id: intent_id.to_string(),
+            near_price: near_price.to_string(),
+            eth_price: eth_price.to_string(),
+            price_timestamp: U64(env::block_timestamp()),
+            idempotency_key: None,
+        }
+    }
+
+    #[test]
+    fn test_execute_arbitrage_batch_skips_failing_intents() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let ready = create(&mut contract, "ETH/USDC", "1.0");
+        let too_strict = create(&mut contract, "NEAR/USDT", "5.0");
+        let paused = create(&mut contract, "BTC/USDC", "1.0");
+        contract.pause_intent(paused.clone());
+
+        let promises = contract.execute_arbitrage_batch(vec![
+            batch_entry(&ready, "3000.0", "2950.0"),
+            batch_entry(&too_strict, "3000.0", "2950.0"),
+            batch_entry(&paused, "3000.0", "2950.0"),
+            batch_entry("99", "3000.0", "2950.0"),
+            batch_entry(&ready, "bad", "2950.0"),
+        ]);
+
+        assert_eq!(promises.len(), 1);
+        assert_eq!(contract.get_executions_for_intent(ready.clone()).len(), 1);
+        assert!(contract.get_executions_for_intent(too_strict.clone()).is_empty());
+        assert!(matches!(contract.get_intent(ready).unwrap().status, IntentStatus::Executed));
+
+        let summary = get_logs().last().unwrap().clone();
+        assert!(summary.starts_with("Batch executed 1 intents, skipped 4"));
+        assert!(summary.contains(&format!("{} (Profit below threshold)", too_strict)));
+        assert!(summary.contains(&format!("{} (Intent must be active)", paused)));
+        assert!(summary.contains("99 (Intent not found)"));
+    }
+
+    #[test]
+    fn test_execute_arbitrage_batch_checks_price_age_and_keys() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.block_timestamp(100_000).build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_price_age(U64(10_000));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let stale = create(&mut contract, "ETH/USDC", "1.0");
+        let keyed = create(&mut contract, "NEAR/USDT", "1.0");
+
+        let mut stale_entry = batch_entry(&stale, "3000.0", "2950.0");
+        stale_entry.price_timestamp = U64(80_000);
+        let mut keyed_entry = batch_entry(&keyed, "3000.0", "2950.0");
+        keyed_entry.idempotency_key = Some("run-1".to_string());
+        let promises = contract.execute_arbitrage_batch(vec![stale_entry, keyed_entry]);
+        assert_eq!(promises.len(), 1);
+        assert!(contract.get_executions_for_intent(stale.clone()).is_empty());
+        let summary = get_logs().last().unwrap().clone();
+        assert!(summary.contains(&format!("{} (Prices are stale)", stale)));
+
+        // A retry with the same key is reported, not executed again
+        let execution_id = contract.get_executions_for_intent(keyed.clone())[0].id.clone();
+        let mut retry = batch_entry(&keyed, "3000.0", "2950.0");
+        retry.idempotency_key = Some("run-1".to_string());
+        assert!(contract.execute_arbitrage_batch(vec![retry]).is_empty());
+        let summary = get_logs().last().unwrap().clone();
+        assert!(summary.contains(&format!("{} (Duplicate of execution {})", keyed, execution_id)));
+    }
+
+    #[test]
+    fn test_swap_success_transfers_profit_to_user() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.execute_arbitrage(
+            intent_id,
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(1_000),
+            None,
+        );
+        // Nothing is credited until the swap callback succeeds
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), U128(0));
+
+        let settled = settle_filled(&mut contract);
+        assert!(matches!(settled, PromiseOrValue::Promise(_)));
+        drop(settled);
+
+        let profit = contract.get_total_profit(accounts(1), accounts(0)).0;
+        assert!(profit > 0);
+        assert_eq!(contract.get_claimable_profit(accounts(1), accounts(0)), U128(0));
+        // The notional comes back along with the profit
+        let expected = NearToken::from_yoctonear(1_000 + profit);
+        assert!(get_created_receipts().iter().any(|receipt| {
+            receipt.receiver_id == accounts(1)
+                && receipt.actions.iter().any(|action| {
+                    matches!(action, MockAction::Transfer { deposit, .. } if *deposit == expected)
+                })
+        }));
+    }
+
+    #[test]
+    fn test_swap_failure_releases_notional_without_profit() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let full = U128(NearToken::from_near(1).as_yoctonear());
+        let _ = contract.execute_arbitrage(
+            intent_id.clone(),
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            full,
+            None,
+        );
+        let intent = contract.get_intent(intent_id.clone()).unwrap();
+        assert!(matches!(intent.status, IntentStatus::Executed));
+
+        let settled = settle_latest(&mut contract, Err(PromiseError::Failed));
+        assert!(matches!(settled, PromiseOrValue::Value(false)));
+
+        let intent = contract.get_intent(intent_id).unwrap();
+        assert!(matches!(intent.status, IntentStatus::Active));
+        assert_eq!(intent.executed_amount, U128(0));
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), U128(0));
+        assert_eq!(contract.get_global_stats().total_volume, U128(0));
+        assert_eq!(contract.intent_deposits, full);
+    }
+
+    #[test]
+    fn test_swap_output_lowers_simulated_profit() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.attached_deposit(NearToken::from_near(10)).build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        // Simulated profit 20 * 0.8 * 0.3 = 4.8, capped at the 3 NEAR notional
+        let input = NearToken::from_near(3).as_yoctonear();
+
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).build());
+        for output in [
+            input + 2 * 10u128.pow(24),
+            input + 10u128.pow(24),
+            input + 5 * 10u128.pow(24),
+        ] {
+            let _ = contract.execute_arbitrage(
+                intent_id.clone(),
+                "40.0".to_string(),
+                "20.0".to_string(),
+                U64(env::block_timestamp()),
+                U128(input),
+                None,
+            );
+            let settled = settle_latest(&mut contract, Ok(Some(output)));
+            assert!(matches!(settled, PromiseOrValue::Promise(_)));
+        }
+
+        let profits: Vec<f64> = contract
+            .get_execution_history(accounts(1))
+            .iter()
+            .map(|execution| execution.profit)
+            .collect();
+        // The DEX can't report its way past the cap
+        assert_eq!(profits, vec![2.0, 1.0, to_near(input)]);
+    }
+
+    #[test]
+    fn test_swap_output_below_notional_returns_only_the_output() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.execute_arbitrage(
+            intent_id,
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(1_000),
+            None,
+        );
+
+        let settled = settle_latest(&mut contract, Ok(Some(600)));
+        assert!(matches!(settled, PromiseOrValue::Promise(_)));
+        drop(settled);
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), U128(0));
+        assert_eq!(contract.get_executions_for_intent("1".to_string())[0].profit, 0.0);
+        assert!(get_created_receipts().iter().any(|receipt| {
+            receipt.actions.iter().any(|action| {
+                matches!(action, MockAction::Transfer { deposit, .. }
+                    if *deposit == NearToken::from_yoctonear(600))
+            })
+        }));
+    }
+
+    #[test]
+    fn test_settlement_without_swap_only_returns_the_notional() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.execute_arbitrage(
+            intent_id,
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(1_000),
+            None,
+        );
+
+        let settled = settle_latest(&mut contract, Ok(None));
+        assert!(matches!(settled, PromiseOrValue::Promise(_)));
+        drop(settled);
+        // The profit is recorded, but none of it is split or paid
+        assert!(contract.get_total_profit(accounts(1), accounts(0)).0 > 0);
+        assert_eq!(contract.get_treasury_earnings(), U128(0));
+        assert_eq!(contract.get_global_stats().total_profit_paid, U128(0));
+        let transfers: Vec<NearToken> = get_created_receipts()
+            .iter()
+            .flat_map(|receipt| receipt.actions.iter())
+            .filter_map(|action| match action {
+                MockAction::Transfer { deposit, .. } => Some(*deposit),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(transfers, vec![NearToken::from_yoctonear(1_000)]);
+    }
+
+    #[test]
+    fn test_swap_calls_dex_with_the_notional_attached() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_dex_contract(Some(accounts(4)));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.execute_arbitrage(
+            intent_id,
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(1_000),
+            None,
+        );
+        let receipts = get_created_receipts();
+        let swap = receipts.iter().find(|receipt| receipt.receiver_id == accounts(4)).unwrap();
+        assert!(swap.actions.iter().any(|action| {
+            matches!(action, MockAction::FunctionCallWeight { method_name, attached_deposit, .. }
+                if method_name == b"swap" && *attached_deposit == NearToken::from_yoctonear(1_000))
+        }));
+
+        // The callback reads the output the DEX returned
+        let execution = contract.get_executions_for_intent("1".to_string())[0].clone();
+        let output = 1_000 + to_yocto(execution.profit);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(serde_json::to_vec(&U128(output)).unwrap())],
+        );
+        let settled = contract.on_swap_complete(execution.id, accounts(1), None);
+        assert!(matches!(settled, PromiseOrValue::Promise(_)));
+        drop(settled);
+        let profit = contract.get_total_profit(accounts(1), accounts(0)).0;
+        assert!(profit > 0);
+        assert!(get_created_receipts().iter().any(|receipt| {
+            receipt.receiver_id == accounts(1)
+                && receipt.actions.iter().any(|action| {
+                    matches!(action, MockAction::Transfer { deposit, .. }
+                        if *deposit == NearToken::from_yoctonear(1_000 + profit))
+                })
+        }));
+    }
+
+    #[test]
+    fn test_token_funded_intents_settle_without_swap() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_dex_contract(Some(accounts(4)));
+        allow_token(&mut contract, accounts(3));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.ft_on_transfer(
+            accounts(1),
+            U128(1_000),
+            r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.0"}"#.to_string(),
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let _ = contract.execute_arbitrage(
+            "1".to_string(),
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(1_000),
+            None,
+        );
+        assert!(get_created_receipts().iter().all(|receipt| receipt.receiver_id != accounts(4)));
+
+        // Whatever the result claims, no token profit is paid out of the pool
+        let _ = settle_latest(&mut contract, Ok(Some(1_000_000)));
+        assert_eq!(contract.get_claimable_profit(accounts(1), accounts(3)), U128(1_000));
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(3)), U128(0));
+    }
+
+    #[test]
+    fn test_unparseable_swap_output_settles_nothing() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.execute_arbitrage(
+            intent_id.clone(),
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(1_000),
+            None,
+        );
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"not a number\"".to_vec())],
+        );
+        let execution_id = contract.all_execution_ids.get(0).unwrap();
+        let settled = contract.on_swap_complete(execution_id, accounts(1), None);
+        assert!(matches!(settled, PromiseOrValue::Value(true)));
+        // The notional went to the DEX, so it is neither refunded nor paid
+        let intent = contract.get_intent(intent_id).unwrap();
+        assert_eq!(intent.executed_amount, U128(1_000));
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), U128(0));
+    }
+
+    #[test]
+    fn test_profit_capped_at_executed_notional() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        execute(&mut contract, &intent_id, "1000000", "1", U128(1_000));
+        assert_eq!(contract.get_executions_for_intent(intent_id)[0].profit, to_near(1_000));
+        assert!(contract.get_total_profit(accounts(1), accounts(0)).0 <= 1_000);
+    }
+
+    #[test]
+    fn test_settlement_beyond_free_balance_stays_claimable() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        // Storage staking alone takes more than this balance
+        testing_env!(context
+            .attached_deposit(NearToken::from_yoctonear(0))
+            .account_balance(NearToken::from_yoctonear(1_000))
+            .build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+
+        // The returned notional is held along with the profit
+        let profit = contract.get_total_profit(accounts(1), accounts(0));
+        assert!(profit.0 > 0);
+        let held = U128(1_000 + profit.0);
+        assert_eq!(contract.get_claimable_profit(accounts(1), accounts(0)), held);
+        assert_eq!(contract.reserved_profits, held);
+    }
+
+    #[test]
+    fn test_settlement_payout_counts_towards_circuit_breaker() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_circuit_breaker_threshold(U128(1));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert!(contract.paused);
+    }
+
+    #[test]
+    fn test_auto_pause_after_consecutive_failures() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_consecutive_failures(3);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let fail = |contract: &mut ArbitrageContract| {
+            let _ = contract.execute_arbitrage(
+                intent_id.clone(),
+                "3000.0".to_string(),
+                "2950.0".to_string(),
+                U64(env::block_timestamp()),
+                U128(1_000),
+                None,
+            );
+            let _ = settle_latest(contract, Err(PromiseError::Failed));
+        };
+
+        fail(&mut contract);
+        fail(&mut contract);
+        let intent = contract.get_intent(intent_id.clone()).unwrap();
+        assert_eq!(intent.consecutive_failures, 2);
+        assert!(matches!(intent.status, IntentStatus::Active));
+
+        // A success in between resets the streak
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_intent(intent_id.clone()).unwrap().consecutive_failures, 0);
+
+        fail(&mut contract);
+        fail(&mut contract);
+        fail(&mut contract);
+        let intent = contract.get_intent(intent_id).unwrap();
+        assert_eq!(intent.consecutive_failures, 3);
+        assert!(matches!(intent.status, IntentStatus::Paused));
+    }
+
+    #[test]
+    fn test_daily_execution_limit() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_executions_per_day(2);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert!(!contract.is_executable(intent_id.clone(), "3000.0".into(), "2950.0".into()));
+
+        testing_env!(context.block_timestamp(NANOS_PER_DAY).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_executions_for_intent(intent_id).len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Daily execution limit reached")]
+    fn test_daily_execution_limit_rejects_over_cap() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_executions_per_day(1);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+    }
+
+    #[test]
+    fn test_dump_emits_every_record_once() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+
+        for user in 1..4 {
+            testing_env!(context.predecessor_account_id(accounts(user)).build());
+            for _ in 0..3 {
+                let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+                execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+            }
+        }
+
+        let cursors = contract.get_dump_cursors();
+        assert_eq!(cursors.total_intents, 9);
+        assert_eq!(cursors.total_executions, 9);
+
+        let mut intent_ids = Vec::new();
+        let mut from_index = 0;
+        while from_index < cursors.total_intents {
+            let page = contract.dump_intents(from_index, 4);
+            intent_ids.extend(page.into_iter().map(|intent| intent.id));
+            from_index += 4;
+        }
+        let mut execution_ids = Vec::new();
+        let mut from_index = 0;
+        while from_index < cursors.total_executions {
+            let page = contract.dump_executions(from_index, 4);
+            execution_ids.extend(page.into_iter().map(|execution| execution.id));
+            from_index += 4;
+        }
+
+        let expected: Vec<String> = (1..=9).map(|id| id.to_string()).collect();
+        assert_eq!(intent_ids, expected);
+        execution_ids.sort();
+        execution_ids.dedup();
+        assert_eq!(execution_ids.len(), 9);
+        assert!(contract.dump_intents(9, 4).is_empty());
+    }
+
+    #[test]
+    fn test_get_executions_above_profit() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "0.5");
+        // Spreads small enough to stay under the notional cap, so profits differ
+        let amount = U128(NearToken::from_near(1).as_yoctonear() / 5);
+        for eth_price in ["2.94", "2.98", "2.90", "2.97"] {
+            execute(&mut contract, &intent_id, "3.0", eth_price, amount);
+        }
+        let history = contract.get_execution_history(accounts(1));
+        let ids = |executions: Vec<ArbitrageExecution>| -> Vec<String> {
+            executions.into_iter().map(|execution| execution.id).collect()
+        };
+
+        // Strictly above the smallest profit, from the 0.02 spread
+        let min_profit = U128(to_yocto(history[1].profit));
+        let above = contract.get_executions_above_profit(accounts(1), min_profit, 0, 10);
+        let expected = [0, 2, 3].map(|index| history[index].id.clone());
+        assert_eq!(ids(above), expected);
+
+        // Pages scan a window of the user's list before filtering
+        let first_page = contract.get_executions_above_profit(accounts(1), min_profit, 0, 2);
+        let second_page = contract.get_executions_above_profit(accounts(1), min_profit, 2, 2);
+        assert_eq!(ids(first_page), vec![history[0].id.clone()]);
+        assert_eq!(ids(second_page), vec![history[2].id.clone(), history[3].id.clone()]);
+
+        let top = U128(to_yocto(history[2].profit));
+        assert!(contract.get_executions_above_profit(accounts(1), top, 0, 10).is_empty());
+        assert!(contract.get_executions_above_profit(accounts(2), U128(0), 0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_get_average_profit() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        assert_eq!(contract.get_average_profit(accounts(1)), U128(0));
+
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        execute(&mut contract, &intent_id, "3000.0", "2900.0", U128(1_000));
+        execute(&mut contract, &intent_id, "3000.0", "2960.0", U128(1_000));
+
+        let total: u128 = contract
+            .get_execution_history(accounts(1))
+            .iter()
+            .map(|execution| to_yocto(execution.profit))
+            .sum();
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), U128(total));
+        assert_eq!(contract.get_average_profit(accounts(1)), U128(total / 3));
+        assert_eq!(contract.get_average_profit(accounts(2)), U128(0));
+    }
+
+    #[test]
+    fn test_get_last_execution_time() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        assert_eq!(contract.get_last_execution_time(accounts(1)), None);
+
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        testing_env!(context.block_timestamp(1_000).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_last_execution_time(accounts(1)), Some(U64(1_000)));
+
+        testing_env!(context.block_timestamp(5_000).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_last_execution_time(accounts(1)), Some(U64(5_000)));
+        assert_eq!(contract.get_last_execution_time(accounts(2)), None);
+    }
+
+    #[test]
+    fn test_intents_needing_attention_reports_each_reason() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(1_000).build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let window = NANOS_PER_DAY;
+
+        let expiring = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            None,
+            None,
+            Some(U64(1_000 + 3 * window)),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        let paused = create(&mut contract, "ETH/USDC", "1.0");
+        contract.pause_intent(paused.clone());
+        let idle = create(&mut contract, "ETH/USDC", "1.0");
+        let executed = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &executed, "3000.0", "2950.0", U128(1_000));
+        assert!(contract.get_intents_needing_attention(accounts(1)).is_empty());
+
+        testing_env!(context.block_timestamp(1_000 + 2 * window).build());
+        assert_eq!(
+            contract.get_intents_needing_attention(accounts(1)),
+            vec![
+                (expiring.clone(), "expiring_soon".to_string()),
+                (paused.clone(), "long_paused".to_string()),
+                (idle, "never_executed".to_string()),
+            ]
+        );
+
+        contract.resume_intent(paused.clone());
+        assert!(contract
+            .get_intents_needing_attention(accounts(1))
+            .contains(&(paused, "never_executed".to_string())));
+
+        testing_env!(context.block_timestamp(1_000 + 3 * window).build());
+        assert!(!contract
+            .get_intents_needing_attention(accounts(1))
+            .iter()
+            .any(|(intent_id, _)| *intent_id == expiring));
+    }
+
+    #[test]
+    fn test_attention_window_is_configurable() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(1_000).build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.block_timestamp(1_000 + 100).build());
+        assert!(contract.get_intents_needing_attention(accounts(1)).is_empty());
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_attention_window(U64(100));
+        assert_eq!(
+            contract.get_intents_needing_attention(accounts(1)),
+            vec![(intent_id, "never_executed".to_string())]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only contract owner or Admin role can call this method")]
+    fn test_set_attention_window_requires_admin() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_attention_window(U64(100));
+    }
+
+    #[test]
+    fn test_interval_gates_is_executable() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(1_000).build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.set_intent_interval(intent_id.clone(), Some(U64(500)));
+        let executable = |contract: &ArbitrageContract| {
+            contract.is_executable(intent_id.clone(), "3000.0".to_string(), "2950.0".to_string())
+        };
+
+        assert!(executable(&contract));
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert!(!executable(&contract));
+
+        testing_env!(context.block_timestamp(1_499).build());
+        assert!(!executable(&contract));
+        testing_env!(context.block_timestamp(1_500).build());
+        assert!(executable(&contract));
+
+        contract.set_intent_interval(intent_id.clone(), None);
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert!(executable(&contract));
+    }
+
+    #[test]
+    #[should_panic(expected = "Execution interval not elapsed")]
+    fn test_execution_before_interval_rejected() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(1_000).build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.set_intent_interval(intent_id.clone(), Some(U64(500)));
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+
+        testing_env!(context.block_timestamp(1_200).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+    }
+
+    #[test]
+    fn test_get_execution_net() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_fee_basis_points(500);
+        assert!(contract.get_execution_net("exec_1".to_string()).is_none());
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let deposit = NearToken::from_near(1).as_yoctonear();
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(deposit));
+
+        let execution_id = contract.get_execution_history(accounts(1))[0].id.clone();
+        let view = contract.get_execution_net(execution_id.clone()).unwrap();
+        let profit = to_yocto(view.execution.profit);
+        let gas_fees = to_yocto(view.execution.gas_fees);
+        assert!(profit > gas_fees);
+        assert_eq!(view.fee.0, profit / 20);
+        assert_eq!(view.net_profit.0, profit - gas_fees - profit / 20);
+
+        let json = serde_json::to_value(&view).unwrap();
+        assert_eq!(json["id"], execution_id);
+        assert_eq!(json["net_profit"], view.net_profit.0.to_string());
+    }
+
+    #[test]
+    fn test_get_user_intents_sorted() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let mut ids = Vec::new();
+        for timestamp in 1..=5 {
+            testing_env!(context.block_timestamp(timestamp).build());
+            ids.push(create(&mut contract, "ETH/USDC", "1.0"));
+        }
+        let page = |contract: &ArbitrageContract, ascending, from_index, limit| -> Vec<String> {
+            contract
+                .get_user_intents_sorted(accounts(1), ascending, from_index, limit)
+                .into_iter()
+                .map(|intent| intent.id)
+                .collect()
+        };
+
+        assert_eq!(page(&contract, true, 0, 3), ids[..3]);
+        assert_eq!(page(&contract, true, 3, 3), ids[3..]);
+        assert_eq!(page(&contract, false, 0, 2), [ids[4].clone(), ids[3].clone()]);
+        assert_eq!(page(&contract, false, 4, 10), [ids[0].clone()]);
+        assert!(page(&contract, false, 5, 10).is_empty());
+        assert!(contract.get_user_intents_sorted(accounts(2), true, 0, 10).is_empty());
+
+        // Cancelling keeps the rest in order
+        let _ = contract.cancel_intent(ids.remove(1));
+        assert_eq!(page(&contract, true, 0, 10), ids);
+    }
+
+    #[test]
+    fn test_transferred_intent_keeps_creation_order() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(1).build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let transferred = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.predecessor_account_id(accounts(2)).block_timestamp(2).build());
+        let newer = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.transfer_intent(transferred.clone(), accounts(2));
+        let ids: Vec<String> = contract
+            .get_user_intents_sorted(accounts(2), true, 0, 10)
+            .into_iter()
+            .map(|intent| intent.id)
+            .collect();
+        assert_eq!(ids, [transferred, newer]);
+    }
+
+    #[test]
+    fn test_export_user_data() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let first = create(&mut contract, "ETH/USDC", "1.0");
+        let second = create(&mut contract, "NEAR/USDT", "1.0");
+        execute(&mut contract, &first, "3000.0", "2950.0", U128(1_000));
+        execute(&mut contract, &second, "3000.0", "2950.0", U128(1_000));
+
+        let export = contract.export_user_data(accounts(1), 0, 100);
+        let intents = contract.get_user_intents(accounts(1));
+        let executions = contract.get_execution_history(accounts(1));
+        assert_eq!(
+            export.intents.iter().map(|intent| &intent.id).collect::<Vec<_>>(),
+            intents.iter().map(|intent| &intent.id).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            export.executions.iter().map(|execution| &execution.id).collect::<Vec<_>>(),
+            executions.iter().map(|execution| &execution.id).collect::<Vec<_>>()
+        );
+        assert_eq!(export.total_profit, contract.get_total_profit(accounts(1), accounts(0)));
+
+        let page = contract.export_user_data(accounts(1), 1, 1);
+        assert_eq!(page.intents[0].id, second);
+        assert_eq!(page.executions.len(), 1);
+        assert!(contract.export_user_data(accounts(2), 0, 100).intents.is_empty());
+    }
+
+    #[test]
+    fn test_execution_tx_hash_is_deterministic_and_unique() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        assert_eq!(pseudo_tx_hash(7), pseudo_tx_hash(7));
+
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let counter = contract.next_execution_id;
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+
+        let executions = contract.get_executions_for_intent(intent_id);
+        assert_eq!(executions[0].tx_hash, pseudo_tx_hash(counter));
+        assert_eq!(executions[1].tx_hash, pseudo_tx_hash(counter + 1));
+        assert_ne!(executions[0].tx_hash, executions[1].tx_hash);
+    }
+
+    #[test]
+    fn test_tx_hash_does_not_depend_on_random_seed() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.random_seed([7; 32]).build());
+        let seeded = pseudo_tx_hash(1);
+        testing_env!(context.random_seed([0; 32]).build());
+        let zeroed = pseudo_tx_hash(1);
+
+        assert_eq!(seeded, zeroed);
+        assert_eq!(zeroed.len(), 64);
+    }
+
+    #[test]
+    fn test_estimate_execution_cost() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let contract = ArbitrageContract::new(accounts(0));
+
+        let gas = GAS_FOR_DEX_SWAP.as_gas() + GAS_FOR_CROSS_CHAIN_CALL.as_gas();
+        assert_eq!(contract.estimate_execution_cost(), U128(gas as u128 * GAS_PRICE_YOCTO));
+        // 250 TGas at 0.1 Gyocto per gas
+        assert_eq!(contract.estimate_execution_cost(), U128(25_000_000_000_000_000_000_000));
+    }
+
+    #[test]
+    fn test_min_profitable_spread() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let one_near = NearToken::from_near(1).as_yoctonear();
+        let mut contract = ArbitrageContract::new(accounts(0));
+
+        // 0.025 NEAR of gas over an 80% share of the spread: 0.03125 breaks even
+        let spread = contract.min_profitable_spread("ETH/USDC".to_string(), U128(one_near));
+        assert!((31_250..=31_251).contains(&spread.0));
+
+        // 0.025 / (100% * 95%) = 0.02631578..
+        contract.set_profit_factor_bps(10_000);
+        contract.set_fee_basis_points(500);
+        let spread = contract.min_profitable_spread("ETH/USDC".to_string(), U128(one_near / 2));
+        assert_eq!(spread, U128(26_316));
+    }
+
+    #[test]
+    fn test_min_profitable_spread_for_intent() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let one_near = NearToken::from_near(1).as_yoctonear();
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let spread = contract.min_profitable_spread_for_intent(intent_id.clone(), U128(one_near));
+        assert!((31_250..=31_251).contains(&spread.0));
+
+        // 0.025 / (50% of the deposit * 100% * 95% * 90%) = 0.05847953..
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_profit_factor_bps(10_000);
+        contract.set_fee_basis_points(500);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_keeper_fee_bps(intent_id.clone(), 1_000);
+        let spread = contract.min_profitable_spread_for_intent(intent_id, U128(one_near / 2));
+        assert_eq!(spread, U128(58_480));
+    }
+
+    #[test]
+    #[should_panic(expected = "No spread is profitable")]
+    fn test_min_profitable_spread_rejects_notional_below_cost() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let contract = ArbitrageContract::new(accounts(0));
+        // Profit can't exceed the 0.01 NEAR executed, which won't cover 0.025 of gas
+        let notional = U128(NearToken::from_millinear(10).as_yoctonear());
+        contract.min_profitable_spread("ETH/USDC".to_string(), notional);
+    }
+
+    #[test]
+    fn test_balance_breakdown_sums_to_total() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.account_balance(NearToken::from_near(100)).build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        execute(&mut contract, &intent_id, "3000.0", "2900.0", U128(2_000));
+
+        let breakdown = contract.get_balance_breakdown();
+        assert_eq!(breakdown.total.0, env::account_balance().as_yoctonear());
+        assert_eq!(breakdown.reserved_profits, contract.reserved_profits);
+        let remaining = NearToken::from_near(1).as_yoctonear() - 3_000;
+        assert_eq!(breakdown.intent_deposits, U128(remaining));
+        assert!(breakdown.storage_reserved.0 > 0);
+        assert_eq!(
+            breakdown.reserved_profits.0
+                + breakdown.treasury_earnings.0
+                + breakdown.intent_deposits.0
+                + breakdown.storage_reserved.0
+                + breakdown.free.0,
+            breakdown.total.0
+        );
+    }
+
+    #[test]
+    fn test_get_contract_info_reflects_configuration() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let info = contract.get_contract_info();
+        for key in ["paused", "fee_basis_points", "oracle_configured", "dex_configured"] {
+            assert!(info.get(key).is_some(), "missing key {}", key);
+        }
+        assert_eq!(info["paused"], false);
+        assert_eq!(info["fee_basis_points"], 0);
+        assert_eq!(info["oracle_configured"], false);
+        assert_eq!(info["dex_configured"], false);
+        assert_eq!(info["version"], format!("{}.0.0", STATE_VERSION));
+
+        contract.set_paused(true);
+        contract.set_fee_basis_points(250);
+        contract.set_oracle_accounts(vec![accounts(4)]);
+        contract.set_dex_contract(Some(accounts(5)));
+        let info = contract.get_contract_info();
+        assert_eq!(info["paused"], true);
+        assert_eq!(info["fee_basis_points"], 250);
+        assert_eq!(info["oracle_configured"], true);
+        assert_eq!(info["dex_configured"], true);
+    }
+
+    fn oracle_response(near_price: &str, eth_price: &str, timestamp: u64) -> PromiseResult {
+        let quote = OraclePrice {
+            near_price: near_price.to_string(),
+            eth_price: eth_price.to_string(),
+            timestamp: U64(timestamp),
+        };
+        PromiseResult::Successful(serde_json::to_vec(&quote).unwrap())
+    }
+
+    fn oracle_callback_context(context: &mut VMContextBuilder, responses: Vec<PromiseResult>) {
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            responses,
+        );
+    }
+
+    #[test]
+    fn test_execute_with_oracles_queries_every_oracle() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_oracle_accounts(vec![accounts(3), accounts(4), accounts(5)]);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.execute_arbitrage_with_oracles(intent_id, U128(1_000), None);
+
+        let receipts = get_created_receipts();
+        for oracle in [accounts(3), accounts(4), accounts(5)] {
+            assert!(receipts.iter().any(|receipt| receipt.receiver_id == oracle));
+        }
+    }
+
+    #[test]
+    fn test_oracle_prices_use_median() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.block_timestamp(1_000).build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_oracle_accounts(vec![accounts(3), accounts(4), accounts(5)]);
+        contract.set_oracle_quorum(2);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        // The outliers would make any execution look hugely profitable
+        let responses = vec![
+            oracle_response("3000.0", "2950.0", 1_000),
+            oracle_response("9000.0", "100.0", 1_000),
+            oracle_response("2990.0", "2940.0", 1_000),
+        ];
+        oracle_callback_context(&mut context, responses);
+        let _ = contract.on_oracle_prices(intent_id.clone(), U128(1_000), None, accounts(1));
+
+        let executions = contract.get_executions_for_intent(intent_id);
+        assert_eq!(executions.len(), 1);
+        assert_eq!(executions[0].eth_price, 2940.0);
+        assert_eq!(executions[0].near_price, 3000.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only 1 oracle prices received, quorum is 2")]
+    fn test_oracle_prices_require_quorum() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_oracle_accounts(vec![accounts(3), accounts(4), accounts(5)]);
+        contract.set_oracle_quorum(2);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        let responses = vec![
+            oracle_response("3000.0", "2950.0", 0),
+            PromiseResult::Failed,
+            oracle_response("3000.0", "n/a", 0),
+        ];
+        oracle_callback_context(&mut context, responses);
+        let _ = contract.on_oracle_prices(intent_id, U128(1_000), None, accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only 1 oracle prices received, quorum is 2")]
+    fn test_stale_oracle_prices_do_not_count_towards_quorum() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.block_timestamp(10_000).build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_oracle_accounts(vec![accounts(3), accounts(4)]);
+        contract.set_oracle_quorum(2);
+        contract.set_max_price_age(U64(1_000));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        let responses = vec![
+            oracle_response("3000.0", "2950.0", 9_500),
+            oracle_response("3000.0", "2950.0", 5_000),
+        ];
+        oracle_callback_context(&mut context, responses);
+        let _ = contract.on_oracle_prices(intent_id, U128(1_000), None, accounts(1));
+    }
+
+    #[test]
+    fn test_oracle_execution_records_idempotency_key() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_oracle_accounts(vec![accounts(3)]);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let key = Some(format!("{}:retry-1", accounts(1)));
+
+        oracle_callback_context(&mut context, vec![oracle_response("3000.0", "2950.0", 0)]);
+        let first = contract.on_oracle_prices(intent_id.clone(), U128(1_000), key, accounts(1));
+        assert!(matches!(first, PromiseOrValue::Promise(_)));
+        let execution_id = contract.get_executions_for_intent(intent_id.clone())[0].id.clone();
+
+        // A retry is answered before the oracles are asked again
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let retry = Some("retry-1".to_string());
+        match contract.execute_arbitrage_with_oracles(intent_id.clone(), U128(1_000), retry) {
+            PromiseOrValue::Value(id) => assert_eq!(id, execution_id),
+            PromiseOrValue::Promise(_) => panic!("duplicate key queried the oracles again"),
+        }
+        assert_eq!(contract.get_executions_for_intent(intent_id).len(), 1);
+    }
+
+    fn create_limit_order(
+        contract: &mut ArbitrageContract,
+        target_price: &str,
+        direction: PriceDirection,
+    ) -> String {
+        contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("50.0".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(target_price.to_string()),
+            Some(direction),
+        )
+    }
+
+    #[test]
+    fn test_limit_order_above_target() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create_limit_order(&mut contract, "3000", PriceDirection::Above);
+        let kind = contract.get_intent(intent_id.clone()).unwrap().kind;
+        let expected = IntentKind::LimitOrder {
+            target_price: 3000.0,
+            direction: PriceDirection::Above,
+        };
+        assert_eq!(kind, expected);
+
+        // The 50% spread threshold is ignored for limit orders
+        let executable = |near_price: &str| {
+            contract.is_executable(intent_id.clone(), near_price.into(), "2990.0".into())
+        };
+        assert!(!executable("2999.0"));
+        assert!(executable("3000.0"));
+        assert!(executable("3100.0"));
+
+        execute(&mut contract, &intent_id, "3001.0", "2990.0", U128(1_000));
+        assert_eq!(contract.get_executions_for_intent(intent_id).len(), 1);
+    }
+
+    #[test]
+    fn test_limit_order_below_target() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create_limit_order(&mut contract, "3000", PriceDirection::Below);
+
+        let executable = |near_price: &str| {
+            contract.is_executable(intent_id.clone(), near_price.into(), "2990.0".into())
+        };
+        assert!(!executable("3000.5"));
+        assert!(executable("3000.0"));
+        assert!(executable("2900.0"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Target price not reached")]
+    fn test_limit_order_rejects_execution_before_crossing() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create_limit_order(&mut contract, "3000", PriceDirection::Below);
+        execute(&mut contract, &intent_id, "3050.0", "2990.0", U128(1_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "target_price and direction must be given together")]
+    fn test_target_price_requires_direction() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some("3000".to_string()),
+            None,
+        );
+    }
+
+    fn create_conditional(contract: &mut ArbitrageContract, expected: &str) -> String {
+        let condition = ExternalCondition {
+            contract: accounts(4),
+            method: "get_utilization".to_string(),
+            expected: expected.to_string(),
+        };
+        contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(condition),
+            None,
+            None,
+        )
+    }
+
+    fn condition_response(
+        context: &mut VMContextBuilder,
+        contract: &mut ArbitrageContract,
+        intent_id: &str,
+        result: &str,
+    ) -> PromiseOrValue<String> {
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(result.as_bytes().to_vec())],
+        );
+        contract.on_condition_checked(
+            intent_id.to_string(),
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U128(1_000),
+            None,
+            accounts(1),
+        )
+    }
+
+    #[test]
+    fn test_conditional_intent_executes_when_condition_holds() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create_conditional(&mut contract, "low");
+
+        // Nothing executes until the condition result comes back
+        let _ = contract.execute_arbitrage(
+            intent_id.clone(),
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(1_000),
+            None,
+        );
+        assert!(contract.get_executions_for_intent(intent_id.clone()).is_empty());
+        assert!(get_created_receipts().iter().any(|receipt| receipt.receiver_id == accounts(4)));
+
+        let _ = condition_response(&mut context, &mut contract, &intent_id, "\"low\"");
+        let executions = contract.get_executions_for_intent(intent_id);
+        assert_eq!(executions.len(), 1);
+        assert_eq!(executions[0].amount, U128(1_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "External condition not met")]
+    fn test_conditional_intent_rejected_when_condition_fails() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create_conditional(&mut contract, "true");
+
+        let _ = condition_response(&mut context, &mut contract, &intent_id, "false");
+    }
+
+    #[test]
+    fn test_external_condition_matching() {
+        let condition = ExternalCondition {
+            contract: accounts(4),
+            method: "get_utilization".to_string(),
+            expected: "42".to_string(),
+        };
+        assert!(condition.is_met(b"42"));
+        assert!(condition.is_met(b"\"42\""));
+        assert!(!condition.is_met(b"43"));
+        assert!(!condition.is_met(b""));
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&mut [2950.0, 100.0, 2940.0]), 2940.0);
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), 2.5);
+    }
+
+    #[test]
+    fn test_intent_dex_overrides_global_dex() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_dex_contract(Some(accounts(4)));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(accounts(5)),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(contract.get_intent(intent_id.clone()).unwrap().dex, Some(accounts(5)));
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+
+        let receipts = get_created_receipts();
+        assert!(receipts.iter().any(|receipt| receipt.receiver_id == accounts(5)));
+        assert!(!receipts.iter().any(|receipt| receipt.receiver_id == accounts(4)));
+    }
+
+    #[test]
+    fn test_min_price_diff_applies_alongside_threshold() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let is_executable = |contract: &ArbitrageContract, intent_id: &String| {
+            contract.is_executable(intent_id.clone(), "3000.0".into(), "2950.0".into())
+        };
+
+        // A 50 gap is ~1.69%: enough for a 1% threshold, not for 2%
+        let both_pass = create_with_min_price_diff(&mut contract, "1.0", "40");
+        assert_eq!(contract.get_intent(both_pass.clone()).unwrap().min_price_diff, Some(40.0));
+        assert!(is_executable(&contract, &both_pass));
+        let gap_too_small = create_with_min_price_diff(&mut contract, "1.0", "100");
+        assert!(!is_executable(&contract, &gap_too_small));
+        let percentage_too_small = create_with_min_price_diff(&mut contract, "2.0", "10");
+        assert!(!is_executable(&contract, &percentage_too_small));
+    }
+
+    #[test]
+    #[should_panic(expected = "Price difference below minimum")]
+    fn test_execute_rejects_gap_below_min_price_diff() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+
+        let intent_id = create_with_min_price_diff(&mut contract, "1.0", "100");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "min_price_diff must be a finite positive number")]
+    fn test_create_intent_rejects_negative_min_price_diff() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        create_with_min_price_diff(&mut contract, "1.0", "-5");
+    }
+
+    #[test]
+    fn test_pause_all_intents() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+
+        let active = create(&mut contract, "ETH/USDC", "1.0");
+        let also_active = create(&mut contract, "NEAR/USDT", "1.0");
+        let paused = create(&mut contract, "BTC/USDC", "1.0");
+        contract.pause_intent(paused.clone());
+        let executed = create(&mut contract, "ETH/USDC", "1.0");
+        let deposit = U128(NearToken::from_near(1).as_yoctonear());
+        execute(&mut contract, &executed, "3000.0", "2950.0", deposit);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let other_user = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert_eq!(contract.pause_all_intents(), 2);
+        for intent_id in [&active, &also_active, &paused] {
+            let intent = contract.get_intent(intent_id.clone()).unwrap();
+            assert!(matches!(intent.status, IntentStatus::Paused));
+        }
+        let executed = contract.get_intent(executed).unwrap();
+        assert!(matches!(executed.status, IntentStatus::Executed));
+        let other_user = contract.get_intent(other_user).unwrap();
+        assert!(matches!(other_user.status, IntentStatus::Active));
+        assert_eq!(contract.pause_all_intents(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn test_paused_contract_rejects_new_intents() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_paused(true);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        create(&mut contract, "ETH/USDC", "1.0");
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn test_paused_contract_rejects_execution() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_paused(true);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+    }
+
+    #[test]
+    fn test_get_global_stats() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let first = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &first, "3000.0", "2950.0", U128(1_000));
+        execute(&mut contract, &first, "3100.0", "2950.0", U128(500));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let second = create(&mut contract, "NEAR/USDC", "1.0");
+        create(&mut contract, "NEAR/USDC", "1.0");
+        execute(&mut contract, &second, "3000.0", "2900.0", U128(2_000));
+        // A failed swap keeps its execution id but isn't counted
+        let _ = contract.execute_arbitrage(
+            second.clone(),
+            "3000.0".to_string(),
+            "2900.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(1_000),
+            None,
+        );
+        let _ = settle_latest(&mut contract, Err(PromiseError::Failed));
+
+        let stats = contract.get_global_stats();
+        assert_eq!(stats.total_intents, 3);
+        assert_eq!(stats.total_executions, 3);
+        assert_eq!(contract.get_contract_info()["total_executions"], 3);
+        assert_eq!(stats.total_volume, U128(3_500));
+        assert_eq!(
+            stats.total_profit_paid.0,
+            contract.get_total_profit(accounts(1), accounts(0)).0
+                + contract.get_total_profit(accounts(2), accounts(0)).0
+        );
+        assert!(stats.total_profit_paid.0 > 0);
+    }
+
+    #[test]
+    fn test_admin_pause_intent() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.admin_pause_intent(intent_id.clone());
+        let intent = contract.get_intent(intent_id.clone()).unwrap();
+        assert!(matches!(intent.status, IntentStatus::Paused));
+        let event = event_payload(get_logs().last().unwrap());
+        assert_eq!(event["event"], "admin_action");
+        assert_eq!(event["data"][0]["action"], "pause_intent");
+        assert_eq!(event["data"][0]["intent_id"], intent_id);
+
+        // Without the lock the intent owner can resume
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.resume_intent(intent_id.clone());
+        assert!(matches!(contract.get_intent(intent_id).unwrap().status, IntentStatus::Active));
+    }
+
+    #[test]
+    #[should_panic(expected = "Intent is locked by the contract owner")]
+    fn test_admin_locked_intent_cannot_be_resumed() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.admin_pause_intent(intent_id.clone());
+        contract.admin_set_intent_lock(intent_id.clone(), true);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.resume_intent(intent_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only contract owner")]
+    fn test_admin_pause_intent_requires_owner() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.admin_pause_intent(intent_id);
+    }
+
+    #[test]
+    fn test_owner_emergency_withdraw_surplus() {
+        let mut context = get_context(accounts(1));
+        context.account_balance(NearToken::from_near(100));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        create(&mut contract, "ETH/USDC", "1.0");
+        contract.credit_claimable_profit(&accounts(1), &accounts(0), 1_000);
+        contract.treasury_earnings = U128(500);
+        let reserved = contract.reserved_profits.0;
+        assert_eq!(reserved, contract.get_claimable_profit(accounts(1), accounts(0)).0);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(NearToken::from_yoctonear(0))
+            .build());
+        let deposit = NearToken::from_near(1).as_yoctonear();
+        let surplus = contract.free_balance();
+        assert!(surplus <= NearToken::from_near(100).as_yoctonear() - reserved - 500 - deposit);
+        let _ = contract.owner_emergency_withdraw(U128(surplus), accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount exceeds withdrawable surplus")]
+    fn test_owner_emergency_withdraw_cannot_touch_storage_staking() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context
+            .account_balance(NearToken::from_near(100))
+            .storage_usage(10_000)
+            .build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let balance = NearToken::from_near(100).as_yoctonear();
+        let _ = contract.owner_emergency_withdraw(U128(balance), accounts(2));
+    }
+
+    #[test]
+    fn test_owner_emergency_withdraw_counts_towards_circuit_breaker() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.account_balance(NearToken::from_near(100)).build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_circuit_breaker_threshold(U128(1));
+
+        let _ = contract.owner_emergency_withdraw(U128(1_000), accounts(2));
+        assert!(contract.paused);
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount exceeds withdrawable surplus")]
+    fn test_owner_emergency_withdraw_cannot_touch_user_profits() {
+        let mut context = get_context(accounts(1));
+        context.account_balance(NearToken::from_near(100));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.credit_claimable_profit(&accounts(1), &accounts(0), 1_000);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(NearToken::from_yoctonear(0))
+            .build());
+        let surplus = NearToken::from_near(100).as_yoctonear() - contract.reserved_profits.0;
+        let _ = contract.owner_emergency_withdraw(U128(surplus + 1), accounts(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount exceeds withdrawable surplus")]
+    fn test_owner_emergency_withdraw_cannot_touch_intent_deposits() {
+        let mut context = get_context(accounts(1));
+        context.account_balance(NearToken::from_near(100));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(NearToken::from_yoctonear(0))
+            .build());
+        let surplus = NearToken::from_near(99).as_yoctonear();
+        let _ = contract.owner_emergency_withdraw(U128(surplus + 1), accounts(0));
+    }
+
+    #[test]
+    fn test_scheduled_intent_executes_after_activation() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(1_000).build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            None,
+            Some(U64(5_000)),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(contract.get_active_intents(accounts(1)).is_empty());
+        assert!(contract
+            .preview_execution(intent_id.clone(), "3000.0".to_string(), "2950.0".to_string())
+            .is_none());
+
+        testing_env!(context.block_timestamp(5_000).build());
+        assert_eq!(contract.get_active_intents(accounts(1)).len(), 1);
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_execution_history(accounts(1)).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Intent not yet active")]
+    fn test_scheduled_intent_rejects_early_execution() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.block_timestamp(1_000).build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            None,
+            Some(U64(5_000)),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+    }
+
+    #[test]
+    fn test_create_intent_with_default_threshold() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_default_threshold("2.5".to_string());
+        let defaulted = contract.create_intent(
+            "ETH/USDC".to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        let explicit = create(&mut contract, "ETH/USDC", "1.0");
+
+        assert_eq!(contract.get_intent(defaulted).unwrap().min_profit_threshold, 2.5);
+        assert_eq!(contract.get_intent(explicit).unwrap().min_profit_threshold, 1.0);
+        assert_eq!(contract.get_default_threshold(accounts(1)), Some("2.5".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "No min_profit_threshold given and no default threshold set")]
+    fn test_create_intent_without_threshold_or_default() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.create_intent(
+            "ETH/USDC".to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "min_profit_threshold must be a finite positive number")]
+    fn test_set_default_threshold_rejects_invalid() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_default_threshold("-1".to_string());
+    }
+
+    #[test]
+    fn test_normalize_pair() {
+        assert_eq!(normalize_pair("ETH/USDC"), "ETH/USDC");
+        assert_eq!(normalize_pair("USDC/ETH"), "ETH/USDC");
+        assert_eq!(normalize_pair("USDT/NEAR"), normalize_pair("NEAR/USDT"));
+    }
+
+    #[test]
+    fn test_inverse_pairs_are_equivalent() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_allowed_pair("USDC/ETH".to_string());
+        assert_eq!(contract.get_allowed_pairs(), vec!["ETH/USDC".to_string()]);
+        let bonus = NearToken::from_near(1).as_yoctonear();
+        contract.set_first_execution_bonus(U128(bonus));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let first = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &first, "3000.0", "2950.0", U128(1_000));
+
+        // The inverse ordering passes the whitelist and shares the pair's bonus state
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let inverse = create(&mut contract, "USDC/ETH", "1.0");
+        assert_eq!(contract.get_intent(inverse.clone()).unwrap().token_pair, "ETH/USDC");
+        execute(&mut contract, &inverse, "3000.0", "2950.0", U128(1_000));
+
+        let first_profit = contract.get_total_profit(accounts(1), accounts(0)).0;
+        let inverse_profit = contract.get_total_profit(accounts(2), accounts(0)).0;
+        assert_eq!(first_profit - inverse_profit, bonus);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.remove_allowed_pair("USDC/ETH".to_string());
+        assert!(contract.get_allowed_pairs().is_empty());
+    }
+
+    #[test]
+    fn test_update_intent_pair() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_allowed_pair("ETH/USDC".to_string());
+        contract.add_allowed_pair("NEAR/USDT".to_string());
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+
+        contract.update_intent_pair(intent_id.clone(), "NEAR/USDT".to_string());
+        assert_eq!(contract.get_intent(intent_id.clone()).unwrap().token_pair, "NEAR/USDT");
+        assert_eq!(contract.get_user_active_pairs(accounts(1)), vec!["NEAR/USDT".to_string()]);
+        assert_eq!(contract.get_executions_for_intent(intent_id).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token pair BTC/USDC is not whitelisted")]
+    fn test_update_intent_pair_rejects_unlisted_pair() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_allowed_pair("ETH/USDC".to_string());
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.update_intent_pair(intent_id, "BTC/USDC".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot update pair of an executed intent")]
+    fn test_update_intent_pair_rejects_executed_intent() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let full = U128(NearToken::from_near(1).as_yoctonear());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", full);
+        contract.update_intent_pair(intent_id, "NEAR/USDT".to_string());
+    }
+
+    #[test]
+    fn test_transfer_intent() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let kept = create(&mut contract, "NEAR/USDT", "1.0");
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+
+        contract.transfer_intent(intent_id.clone(), accounts(2));
+
+        let old_owner_intents = contract.get_user_intents(accounts(1));
+        assert_eq!(old_owner_intents.len(), 1);
+        assert_eq!(old_owner_intents[0].id, kept);
+        let new_owner_intents = contract.get_user_intents(accounts(2));
+        assert_eq!(new_owner_intents.len(), 1);
+        assert_eq!(new_owner_intents[0].id, intent_id);
+        assert_eq!(new_owner_intents[0].user, accounts(2));
+        assert_eq!(contract.get_executions_for_intent(intent_id.clone()).len(), 1);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_executions_for_intent(intent_id).len(), 2);
+    }
+
+    #[test]
+    fn test_transfer_intent_moves_deposit_and_key_accounting() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let pk = env::signer_account_pk();
+        let deposit = U128(NearToken::from_near(1).as_yoctonear());
+        assert_eq!(contract.total_deposited.get(&accounts(1)), Some(deposit));
+
+        contract.transfer_intent(intent_id.clone(), accounts(2));
+        assert_eq!(contract.total_deposited.get(&accounts(1)), Some(U128(0)));
+        assert_eq!(contract.total_deposited.get(&accounts(2)), Some(deposit));
+        assert!(contract.get_intents_by_public_key(pk, 0, 10).is_empty());
+        assert!(contract.get_intent(intent_id).unwrap().signer_pk.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Account bob is blocked")]
+    fn test_transfer_intent_rejects_blocked_new_owner() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.block_account(accounts(1));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.transfer_intent(intent_id, accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Active intent limit of 1 reached")]
+    fn test_transfer_intent_respects_new_owner_limit() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_active_intents_per_user(1);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        create(&mut contract, "ETH/USDC", "1.0");
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.transfer_intent(intent_id, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn test_transfer_intent_rejected_while_paused() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_paused(true);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.transfer_intent(intent_id, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Intent has a swap in flight")]
+    fn test_transfer_intent_waits_for_pending_swap() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.execute_arbitrage(
+            intent_id.clone(),
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(1_000),
+            None,
+        );
+        contract.transfer_intent(intent_id, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only intent owner can transfer")]
+    fn test_transfer_intent_requires_owner() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.transfer_intent(intent_id, accounts(2));
+    }
+
+    #[test]
+    fn test_merge_intents() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let keep_id = create(&mut contract, "ETH/USDC", "1.0");
+        let merge_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &keep_id, "3000.0", "2950.0", U128(1_000));
+        execute(&mut contract, &merge_id, "3000.0", "2950.0", U128(2_000));
+        execute(&mut contract, &merge_id, "3000.0", "2950.0", U128(3_000));
+
+        contract.merge_intents(keep_id.clone(), merge_id.clone());
+
+        let history = contract.get_executions_for_intent(keep_id.clone());
+        assert_eq!(history.len(), 3);
+        assert!(history.iter().all(|execution| execution.intent_id == keep_id));
+        assert!(contract.get_executions_for_intent(merge_id.clone()).is_empty());
+
+        let kept = contract.get_intent(keep_id).unwrap();
+        assert_eq!(kept.deposit.0, 2 * NearToken::from_near(1).as_yoctonear());
+        assert_eq!(kept.executed_amount, U128(6_000));
+        assert_eq!(kept.execution_count, 3);
+        assert!(contract.get_intent(merge_id.clone()).is_none());
+        assert_eq!(contract.get_intent_status_detailed(merge_id), "merged");
+        assert_eq!(contract.get_user_intents(accounts(1)).len(), 1);
+        let pk = env::signer_account_pk();
+        assert_eq!(contract.get_intents_by_public_key(pk, 0, 100).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Intent has a swap in flight")]
+    fn test_merge_intents_rejects_swaps_in_flight() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let keep_id = create(&mut contract, "ETH/USDC", "1.0");
+        let merge_id = create(&mut contract, "ETH/USDC", "1.0");
+        let _ = contract.execute_arbitrage(
+            merge_id.clone(),
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            U128(2_000),
+            None,
+        );
+        contract.merge_intents(keep_id, merge_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot merge an executed intent")]
+    fn test_merge_intents_rejects_executed_intent() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let keep_id = create(&mut contract, "ETH/USDC", "1.0");
+        let merge_id = create(&mut contract, "ETH/USDC", "1.0");
+        let full = U128(NearToken::from_near(1).as_yoctonear());
+        execute(&mut contract, &merge_id, "3000.0", "2950.0", full);
+        contract.merge_intents(keep_id, merge_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot merge intents of different kinds")]
+    fn test_merge_intents_rejects_different_kinds() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let keep_id = create(&mut contract, "ETH/USDC", "1.0");
+        let merge_id = create_limit_order(&mut contract, "3000.0", PriceDirection::Above);
+        contract.merge_intents(keep_id, merge_id);
+    }
+
+    #[test]
+    fn test_merge_intents_keeps_settings_and_adds_execution_caps() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let keep_id = create(&mut contract, "ETH/USDC", "1.0");
+        let merge_id = create(&mut contract, "ETH/USDC", "2.0");
+        let capped = |contract: &mut ArbitrageContract, id: &str, max_executions| {
+            let mut intent = contract.intents.get(&id.to_string()).unwrap();
+            intent.max_executions = Some(max_executions);
+            contract.intents.insert(&id.to_string(), &intent);
+        };
+        capped(&mut contract, &keep_id, 2);
+        capped(&mut contract, &merge_id, 3);
+        contract.pause_intent(merge_id.clone());
+
+        contract.merge_intents(keep_id.clone(), merge_id);
+        let kept = contract.get_intent(keep_id).unwrap();
+        assert_eq!(kept.min_profit_threshold, 1.0);
+        assert_eq!(kept.max_executions, Some(5));
+        assert!(matches!(kept.status, IntentStatus::Active));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot merge intents on different pairs")]
+    fn test_merge_intents_rejects_different_pairs() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let keep_id = create(&mut contract, "ETH/USDC", "1.0");
+        let merge_id = create(&mut contract, "NEAR/USDT", "1.0");
+        contract.merge_intents(keep_id, merge_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only intent owner can merge intents")]
+    fn test_merge_intents_rejects_other_owner() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let keep_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(get_context(accounts(2)).build());
+        let merge_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.merge_intents(keep_id, merge_id);
+    }
+
+    #[test]
+    fn test_storage_prefixes_do_not_overlap() {
+        let hash = [7; 32];
+        let keys = vec![
+            StorageKey::Intents,
+            StorageKey::UserIntents,
+            StorageKey::Executions,
+            StorageKey::UserExecutions,
+            StorageKey::UserProfits,
+            StorageKey::CrossChainSignatures,
+            StorageKey::TotalDeposited,
+            StorageKey::ExecutedKeys,
+            StorageKey::IntentExecutions,
+            StorageKey::ClaimableProfits,
+            StorageKey::UserProfitTokens,
+            StorageKey::UserExecutionNonces,
+            StorageKey::AllIntentIds,
+            StorageKey::ReferrerEarnings,
+            StorageKey::SupportedChains,
+            StorageKey::LegacyUserProfits,
+            StorageKey::AllExecutionIds,
+            StorageKey::PairsSeen,
+            StorageKey::UserDefaults,
+            StorageKey::DailyExecutions,
+            StorageKey::UserEarnedTokens,
+            StorageKey::PairStats,
+            StorageKey::ProfitEpochs,
+            StorageKey::ProfitEpochCount,
+            StorageKey::PkIntents,
+            StorageKey::Templates,
+            StorageKey::CancelledIntents,
+            StorageKey::PairMinThreshold,
+            StorageKey::Roles,
+            StorageKey::Blocklist,
+            StorageKey::UserIntentList { account_hash: hash },
+            StorageKey::UserIntentList { account_hash: [8; 32] },
+            StorageKey::UserExecutionList { account_hash: hash },
+            StorageKey::IntentExecutionList { intent_hash: hash },
+            StorageKey::UserProfitTokenList { account_hash: hash },
+            StorageKey::UserEarnedTokenList { account_hash: hash },
+            StorageKey::PkIntentList { key_hash: hash },
+        ];
+        let mut prefixes: Vec<Vec<u8>> =
+            keys.into_iter().map(|key| key.into_storage_key()).collect();
+        // Collections migrated from the old layout keep their byte-string prefixes
+        prefixes.extend(
+            ["intents", "user_intents", "executions", "user_executions", "user_profits"]
+                .map(|prefix| prefix.as_bytes().to_vec()),
+        );
+        prefixes.push(b"cross_chain_sigs".to_vec());
+
+        for (i, a) in prefixes.iter().enumerate() {
+            for (j, b) in prefixes.iter().enumerate() {
+                assert!(i == j || !b.starts_with(a), "prefix {:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_adversarial_account_ids_keep_collections_isolated() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let lookalike: AccountId = "user_executions_alice.near".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let alice_intent = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &alice_intent, "3000.0", "2950.0", U128(1_000));
+
+        testing_env!(get_context(lookalike.clone()).build());
+        let lookalike_intent = create(&mut contract, "NEAR/USDT", "1.0");
+        create(&mut contract, "BTC/USDC", "1.0");
+
+        let alice_intents = contract.get_user_intents(alice.clone());
+        assert_eq!(alice_intents.len(), 1);
+        assert_eq!(alice_intents[0].id, alice_intent);
+        assert_eq!(contract.get_user_intents(lookalike.clone()).len(), 2);
+        assert_eq!(contract.get_execution_history(alice).len(), 1);
+        assert!(contract.get_execution_history(lookalike).is_empty());
+        assert!(contract.get_executions_for_intent(lookalike_intent).is_empty());
+    }
+
+    #[test]
+    fn test_profit_split_between_user_owner_and_referrer() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_fee_basis_points(500);
+        contract.set_referrer_bps(1_000);
+
+        testing_env!(get_context(accounts(1)).build());
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            Some(accounts(2)),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000_003));
+
+        let gross = contract.total_profit_paid.0;
+        let owner_fee = contract.get_treasury_earnings().0;
+        let referrer_fee = contract.get_referrer_earnings(accounts(2)).0;
+        let user_profit = contract.get_total_profit(accounts(1), accounts(0)).0;
+        assert_eq!(owner_fee, gross * 500 / 10_000);
+        assert_eq!(referrer_fee, gross * 1_000 / 10_000);
+        assert_eq!(user_profit + owner_fee + referrer_fee, gross);
+        assert_eq!(contract.reserved_profits.0, referrer_fee);
+    }
+
+    #[test]
+    fn test_fees_credit_treasury() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        assert_eq!(contract.get_treasury(), accounts(0));
+        contract.set_fee_basis_points(500);
+        contract.set_treasury(accounts(3));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000_003));
+        let fee = contract.get_treasury_earnings().0;
+        assert_eq!(fee, contract.total_profit_paid.0 * 500 / 10_000);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let _ = contract.withdraw_treasury();
+        assert_eq!(contract.get_treasury_earnings(), U128(0));
+        let expected = NearToken::from_yoctonear(fee);
+        assert!(get_created_receipts().iter().any(|receipt| {
+            receipt.receiver_id == accounts(3)
+                && receipt.actions.iter().any(|action| {
+                    matches!(action, MockAction::Transfer { deposit, .. } if *deposit == expected)
+                })
+        }));
+        assert!(!get_logs().iter().any(|log| log.starts_with("EVENT_JSON:")));
+
+        // The event waits for the transfer to go through
+        callback_context(&mut context, PromiseResult::Successful(Vec::new()));
+        assert!(contract.on_treasury_withdraw_resolved(accounts(3), U128(fee)));
+        let event = event_payload(get_logs().last().unwrap());
+        assert_eq!(event["event"], "treasury_withdrawn");
+        assert_eq!(event["data"][0]["treasury"], accounts(3).to_string());
+        assert_eq!(event["data"][0]["amount"], fee.to_string());
+        assert_eq!(event["data"][0]["remaining"], "0");
+    }
+
+    #[test]
+    #[should_panic(expected = "Only treasury can withdraw treasury earnings")]
+    fn test_withdraw_treasury_requires_treasury() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_treasury(accounts(3));
+        let _ = contract.withdraw_treasury();
+    }
+
+    #[test]
+    fn test_withdraw_referrer_earnings() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_referrer_bps(2_000);
+
+        testing_env!(get_context(accounts(1)).build());
+        let intent_id = contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            Some(accounts(3)),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert!(contract.get_referrer_earnings(accounts(3)).0 > 0);
+
+        testing_env!(get_context(accounts(3)).build());
+        let _ = contract.withdraw_referrer_earnings();
+        assert_eq!(contract.get_referrer_earnings(accounts(3)), U128(0));
+        assert_eq!(contract.reserved_profits, U128(0));
+    }
+
+    #[test]
+    fn test_no_referrer_leaves_share_with_user() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_referrer_bps(1_000);
+
+        testing_env!(get_context(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), contract.total_profit_paid);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot refer yourself")]
+    fn test_self_referral_rejected() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.create_intent(
+            "ETH/USDC".to_string(),
+            Some("1.0".to_string()),
+            None,
+            None,
+            Some(accounts(1)),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_get_fee_config() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_fee_basis_points(500);
+        contract.set_referrer_bps(1_000);
+        contract.set_default_keeper_fee_bps(50);
+        contract.set_cancellation_fee_bps(200);
+        contract.set_creation_fee(U128(1_000));
+        contract.set_treasury(accounts(4));
+
+        let config = contract.get_fee_config();
+        assert_eq!(config.fee_basis_points, 500);
+        assert_eq!(config.referrer_bps, 1_000);
+        assert_eq!(config.keeper_fee_bps, 50);
+        assert_eq!(config.cancellation_fee_bps, 200);
+        assert_eq!(config.creation_fee, U128(1_000));
+        assert_eq!(config.treasury, accounts(4));
+
+        // New intents start with the default keeper fee
+        testing_env!(context.attached_deposit(NearToken::from_near(2)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        assert_eq!(contract.get_intent(intent_id).unwrap().keeper_fee_bps, 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "Fee and referrer shares cannot exceed 100%")]
+    fn test_fee_and_referrer_share_capped() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_fee_basis_points(6_000);
+        contract.set_referrer_bps(5_000);
+    }
+
+    #[test]
+    fn test_withdraw_releases_reserved_profits() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.credit_claimable_profit(&accounts(1), &accounts(0), 1_000);
+        assert!(contract.reserved_profits.0 > 0);
+
+        contract.withdraw_all_profits();
+        assert_eq!(contract.reserved_profits, U128(0));
+    }
+
+    /// Calls back into the contract with `result` as the resolved promise,
+    /// as the runtime does for a resolve callback.
+    fn callback_context(context: &mut VMContextBuilder, result: PromiseResult) {
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+    }
+
+    #[test]
+    fn test_failed_withdrawal_restores_claimable_profit() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.credit_claimable_profit(&accounts(1), &accounts(0), 1_000);
+        contract.credit_claimable_profit(&accounts(1), &accounts(3), 500);
+        assert_eq!(contract.withdraw_all_profits().len(), 2);
+        assert_eq!(contract.reserved_profits, U128(0));
+
+        callback_context(&mut context, PromiseResult::Failed);
+        assert!(!contract.on_withdraw_resolved(accounts(1), accounts(0), U128(1_000)));
+        assert!(!get_logs().iter().any(|log| log.starts_with("EVENT_JSON:")));
+        assert!(!contract.on_withdraw_resolved(accounts(1), accounts(3), U128(500)));
+        assert_eq!(contract.get_claimable_profit(accounts(1), accounts(0)), U128(1_000));
+        assert_eq!(contract.get_claimable_profit(accounts(1), accounts(3)), U128(500));
+        assert_eq!(contract.reserved_profits, U128(1_000));
+
+        // Restored balances can be withdrawn again
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert_eq!(contract.withdraw_all_profits().len(), 2);
+    }
+
+    #[test]
+    fn test_successful_withdrawal_keeps_balance_cleared() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.credit_claimable_profit(&accounts(1), &accounts(0), 1_000);
+        let _ = contract.withdraw_all_profits();
+
+        callback_context(&mut context, PromiseResult::Successful(Vec::new()));
+        assert!(contract.on_withdraw_resolved(accounts(1), accounts(0), U128(1_000)));
+        assert_eq!(contract.get_claimable_profit(accounts(1), accounts(0)), U128(0));
+        assert_eq!(contract.reserved_profits, U128(0));
+    }
+
+    #[test]
+    fn test_failed_referrer_withdrawal_restores_earnings() {
+        let mut context = get_context(accounts(3));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.referrer_earnings.insert(&accounts(3), &U128(700));
+        contract.reserved_profits = U128(700);
+        let _ = contract.withdraw_referrer_earnings();
+        assert_eq!(contract.get_referrer_earnings(accounts(3)), U128(0));
+
+        callback_context(&mut context, PromiseResult::Failed);
+        assert!(!contract.on_referrer_withdraw_resolved(accounts(3), U128(700)));
+        assert_eq!(contract.get_referrer_earnings(accounts(3)), U128(700));
+        assert_eq!(contract.reserved_profits, U128(700));
+    }
+
+    #[test]
+    fn test_failed_treasury_withdrawal_restores_earnings() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.treasury_earnings = U128(900);
+        let _ = contract.withdraw_treasury();
+        assert_eq!(contract.get_treasury_earnings(), U128(0));
+
+        callback_context(&mut context, PromiseResult::Failed);
+        assert!(!contract.on_treasury_withdraw_resolved(accounts(0), U128(900)));
+        assert_eq!(contract.get_treasury_earnings(), U128(900));
+    }
+
+    #[test]
+    fn test_create_intent_at_max_profit_threshold() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        assert_eq!(contract.get_max_profit_threshold(), 100.0);
+        let intent_id = create(&mut contract, "ETH/USDC", "100");
+        assert_eq!(contract.get_intent(intent_id).unwrap().min_profit_threshold, 100.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "min_profit_threshold exceeds maximum")]
+    fn test_create_intent_above_max_profit_threshold() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_profit_threshold("10".to_string());
+        assert_eq!(contract.get_max_profit_threshold(), 10.0);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        create(&mut contract, "ETH/USDC", "10.5");
+    }
+
+    #[test]
+    fn test_create_intent_at_pair_min_threshold() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_pair_min_threshold("USDC/ETH".to_string(), Some("0.5".to_string()));
+        let pair_min = contract.get_pair_min_threshold("ETH/USDC".to_string());
+        assert_eq!(pair_min, Some("0.5".to_string()));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        create(&mut contract, "ETH/USDC", "0.5");
+        create(&mut contract, "ETH/USDC", "2.0");
+        // Other pairs are unaffected
+        create(&mut contract, "BTC/USDC", "0.1");
+    }
+
+    #[test]
+    #[should_panic(expected = "min_profit_threshold below pair minimum of 0.5")]
+    fn test_create_intent_below_pair_min_threshold() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_pair_min_threshold("ETH/USDC".to_string(), Some("0.5".to_string()));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        create(&mut contract, "ETH/USDC", "0.4");
+    }
+
+    #[test]
+    #[should_panic(expected = "Only contract owner")]
+    fn test_set_max_profit_threshold_requires_owner() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_profit_threshold("999999".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid token_pair: expected BASE/QUOTE")]
+    fn test_create_intent_rejects_empty_pair() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        create(&mut contract, "", "1.0");
+    }
+
+    #[test]
+    fn test_create_intent_rejects_malformed_pairs() {
+        for token_pair in ["ETHUSDC", "ETH/", "/USDC", "ETH/USDC/DAI", "ETH /USDC"] {
+            let context = get_context(accounts(1));
+            testing_env!(context.build());
+            let result = std::panic::catch_unwind(|| {
+                let mut contract = ArbitrageContract::new(accounts(0));
+                create(&mut contract, token_pair, "1.0");
+            });
+            assert!(result.is_err(), "accepted {}", token_pair);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid min_profit_threshold: must be a valid number")]
+    fn test_create_intent_rejects_non_numeric_threshold() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        create(&mut contract, "ETH/USDC", "one percent");
+    }
+
+    #[test]
+    fn test_create_intent_rejects_non_positive_thresholds() {
+        for threshold in ["0", "-1.0", "NaN", "inf"] {
+            let context = get_context(accounts(1));
+            testing_env!(context.build());
+            let result = std::panic::catch_unwind(|| {
+                let mut contract = ArbitrageContract::new(accounts(0));
+                create(&mut contract, "ETH/USDC", threshold);
+            });
+            assert!(result.is_err(), "accepted {}", threshold);
+        }
+    }
+
+    #[test]
+    fn test_execute_rejects_non_finite_prices() {
+        for (near_price, eth_price) in [("nan", "2950.0"), ("3000.0", "NaN"), ("inf", "2950.0")] {
+            let context = get_context(accounts(1));
+            testing_env!(context.build());
+            let result = std::panic::catch_unwind(|| {
+                let mut contract = ArbitrageContract::new(accounts(0));
+                let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+                execute(&mut contract, &intent_id, near_price, eth_price, U128(1_000));
+            });
+            assert!(result.is_err(), "accepted {}/{}", near_price, eth_price);
+        }
+        assert_eq!(
+            try_parse_prices("3000.0", "-inf").unwrap_err(),
+            "Prices must be finite numbers"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "max_profit_threshold must be a finite positive number")]
+    fn test_set_max_profit_threshold_rejects_infinity() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_max_profit_threshold("inf".to_string());
+    }
+
+    #[test]
+    fn test_execute_refunds_attached_deposit() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        let attached = NearToken::from_millinear(250);
+        testing_env!(context.attached_deposit(attached).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert!(get_created_receipts().iter().any(|receipt| {
+            receipt.receiver_id == accounts(1)
+                && receipt.actions.iter().any(|action| {
+                    matches!(action, MockAction::Transfer { deposit, .. } if *deposit == attached)
+                })
+        }));
+
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert!(!get_logs().iter().any(|log| log.starts_with("Refunding unused deposit")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Prices are equal")]
+    fn test_execute_rejects_equal_prices() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "3000.0", U128(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Prices must be positive")]
+    fn test_execute_rejects_zero_price() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "0", "2950.0", U128(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Prices must be positive")]
+    fn test_execute_rejects_negative_price() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        execute(&mut contract, &intent_id, "3000.0", "-5.0", U128(1));
+    }
+
+    #[test]
+    fn test_idempotency_key_prevents_duplicate_execution() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let half = U128(NearToken::from_millinear(500).as_yoctonear());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let first = contract.execute_arbitrage(
+            intent_id.clone(),
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            half,
+            Some("retry-1".to_string()),
+        );
+        assert!(matches!(first, PromiseOrValue::Promise(_)));
+
+        let second = contract.execute_arbitrage(
+            intent_id.clone(),
+            "3000.0".to_string(),
+            "2950.0".to_string(),
+            U64(env::block_timestamp()),
+            half,
+            Some("retry-1".to_string()),
+        );
         let executions = contract.get_execution_history(accounts(1));
+        match second {
+            PromiseOrValue::Value(execution_id) => assert_eq!(execution_id, executions[0].id),
+            PromiseOrValue::Promise(_) => panic!("duplicate key created a new execution"),
+        }
+
         assert_eq!(executions.len(), 1);
-        assert_eq!(executions[0].token_pair, "ETH/USDC");
-        assert!(executions[0].profit > 0.0);
+        assert_eq!(contract.get_intent(intent_id).unwrap().executed_amount, half);
+    }
+
+    #[test]
+    fn test_failed_swap_releases_idempotency_key() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+        let half = U128(NearToken::from_millinear(500).as_yoctonear());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        let submit = |contract: &mut ArbitrageContract| {
+            contract.execute_arbitrage(
+                intent_id.clone(),
+                "3000.0".to_string(),
+                "2950.0".to_string(),
+                U64(env::block_timestamp()),
+                half,
+                Some("retry-1".to_string()),
+            )
+        };
+        let _ = submit(&mut contract);
+        let failed_id = contract.get_execution_history(accounts(1))[0].id.clone();
+        let key = Some(format!("{}:retry-1", accounts(1)));
+        let failure = Err(PromiseError::Failed);
+        let _ = contract.settle_swap(failed_id.clone(), accounts(1), key, failure);
+
+        // The retry executes instead of returning the failed execution
+        assert!(matches!(submit(&mut contract), PromiseOrValue::Promise(_)));
+        let executions = contract.get_execution_history(accounts(1));
+        assert_eq!(executions.len(), 2);
+        assert_ne!(executions[1].id, failed_id);
+        match submit(&mut contract) {
+            PromiseOrValue::Value(execution_id) => assert_eq!(execution_id, executions[1].id),
+            PromiseOrValue::Promise(_) => panic!("duplicate key created a new execution"),
+        }
+    }
+
+    #[test]
+    fn test_conditional_retries_execute_once() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create_conditional(&mut contract, "low");
+
+        // Both retries are accepted before either condition result is back
+        for _ in 0..2 {
+            let _ = contract.execute_arbitrage(
+                intent_id.clone(),
+                "3000.0".to_string(),
+                "2950.0".to_string(),
+                U64(env::block_timestamp()),
+                U128(1_000),
+                Some("retry-1".to_string()),
+            );
+        }
+        assert!(contract.get_executions_for_intent(intent_id.clone()).is_empty());
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"low\"".to_vec())],
+        );
+        let mut resume = || {
+            contract.on_condition_checked(
+                intent_id.clone(),
+                "3000.0".to_string(),
+                "2950.0".to_string(),
+                U128(1_000),
+                Some(format!("{}:retry-1", accounts(1))),
+                accounts(1),
+            )
+        };
+        assert!(matches!(resume(), PromiseOrValue::Promise(_)));
+        assert!(matches!(resume(), PromiseOrValue::Value(_)));
+        assert_eq!(contract.get_executions_for_intent(intent_id).len(), 1);
+    }
+
+    fn event_payload(log: &str) -> serde_json::Value {
+        let json = log.strip_prefix("EVENT_JSON:").expect("not an event log");
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// Signs the `signature_message` of an execution with a fixed test key.
+    fn sign_execution(execution_id: &str, chain_id: u64, nonce: u64) -> (Base64VecU8, PublicKey) {
+        use ed25519_dalek::Signer;
+
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let signature = signing_key.sign(&signature_message(execution_id, chain_id, nonce));
+        let public_key = PublicKey::from_parts(
+            near_sdk::CurveType::ED25519,
+            signing_key.verifying_key().to_bytes().to_vec(),
+        )
+        .unwrap();
+        (Base64VecU8(signature.to_bytes().to_vec()), public_key)
+    }
+
+    #[test]
+    fn test_signature_events() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_supported_chain(1);
+
+        testing_env!(get_context(accounts(2)).build());
+        let (signature, public_key) = sign_execution("7", 1, 9);
+        contract.store_cross_chain_signature("7".to_string(), signature, public_key, 1, 9);
+        let stored = event_payload(get_logs().last().unwrap());
+        assert_eq!(stored["standard"], "arbitrage");
+        assert_eq!(stored["event"], "signature_stored");
+        assert_eq!(stored["data"][0]["execution_id"], "7");
+        assert_eq!(stored["data"][0]["chain_id"], 1);
+        assert_eq!(stored["data"][0]["nonce"], 9);
+
+        assert!(contract.verify_cross_chain_signature("7".to_string()));
+        let verified = event_payload(get_logs().last().unwrap());
+        assert_eq!(verified["event"], "signature_verified");
+        assert_eq!(verified["data"][0]["execution_id"], "7");
+        assert_eq!(verified["data"][0]["result"], true);
+
+        assert!(!contract.verify_cross_chain_signature("8".to_string()));
+        let missing = event_payload(get_logs().last().unwrap());
+        assert_eq!(missing["data"][0]["result"], false);
+    }
+
+    #[test]
+    fn test_verify_cross_chain_signatures_batch() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_supported_chain(1);
+
+        let (signature, public_key) = sign_execution("1", 1, 5);
+        contract.store_cross_chain_signature("1".to_string(), signature, public_key, 1, 5);
+        // Signed for another nonce than the one stored
+        let (signature, public_key) = sign_execution("2", 1, 6);
+        contract.store_cross_chain_signature("2".to_string(), signature, public_key, 1, 7);
+        let (signature, public_key) = sign_execution("3", 1, 5);
+        contract.store_cross_chain_signature("3".to_string(), signature, public_key, 1, 5);
+        let (_, public_key) = sign_execution("4", 1, 5);
+        contract.store_cross_chain_signature(
+            "4".to_string(),
+            Base64VecU8(vec![1, 2, 3]),
+            public_key,
+            1,
+            5,
+        );
+
+        let ids = ["1", "2", "missing", "3", "4"].map(String::from).to_vec();
+        assert_eq!(
+            contract.verify_cross_chain_signatures_batch(ids),
+            vec![true, false, false, true, false]
+        );
+        assert!(contract.verify_cross_chain_signatures_batch(Vec::new()).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "At most 50 signatures can be verified per call")]
+    fn test_verify_signatures_batch_capped() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let contract = ArbitrageContract::new(accounts(0));
+        contract.verify_cross_chain_signatures_batch(vec!["1".to_string(); 51]);
+    }
+
+    #[test]
+    fn test_get_cross_chain_signature() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_supported_chain(1);
+        let public_key = "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp";
+        contract.store_cross_chain_signature(
+            "7".to_string(),
+            Base64VecU8(vec![1, 2, 3]),
+            public_key.parse().unwrap(),
+            1,
+            9,
+        );
+
+        let stored = contract.get_cross_chain_signature("7".to_string()).unwrap();
+        assert_eq!(stored.signature.0, vec![1, 2, 3]);
+        assert_eq!(stored.public_key, public_key);
+        assert_eq!(stored.chain_id, 1);
+        assert_eq!(stored.nonce, 9);
+        assert!(contract.get_cross_chain_signature("8".to_string()).is_none());
+
+        let json = serde_json::to_value(&stored).unwrap();
+        assert_eq!(json["signature"], "AQID");
+        assert_eq!(json["public_key"], public_key);
+    }
+
+    #[test]
+    fn test_store_signature_with_allowed_curve() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_supported_chain(1);
+        assert_eq!(contract.get_allowed_curve_types(), vec![CURVE_TYPE_ED25519]);
+        contract.store_cross_chain_signature(
+            "7".to_string(),
+            Base64VecU8(vec![1, 2, 3]),
+            "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap(),
+            1,
+            1,
+        );
+        assert!(contract.get_cross_chain_signature("7".to_string()).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported public key curve type 1")]
+    fn test_store_signature_rejects_disallowed_curve() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_supported_chain(1);
+        let secp256k1_key =
+            PublicKey::from_parts(near_sdk::CurveType::SECP256K1, vec![7; 64]).unwrap();
+        contract.store_cross_chain_signature(
+            "7".to_string(),
+            Base64VecU8(vec![1, 2, 3]),
+            secp256k1_key,
+            1,
+            1,
+        );
+    }
+
+    #[test]
+    fn test_supported_chains() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_supported_chain(1);
+        contract.add_supported_chain(137);
+        contract.add_supported_chain(1);
+        assert_eq!(contract.get_supported_chains(), vec![1, 137]);
+
+        let (signature, public_key) = sign_execution("7", 137, 1);
+        contract.store_cross_chain_signature("7".to_string(), signature, public_key, 137, 1);
+        assert!(contract.verify_cross_chain_signature("7".to_string()));
+
+        contract.remove_supported_chain(1);
+        assert_eq!(contract.get_supported_chains(), vec![137]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported chain_id 56")]
+    fn test_store_signature_rejects_unsupported_chain() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_supported_chain(1);
+        contract.store_cross_chain_signature(
+            "7".to_string(),
+            Base64VecU8(vec![1, 2, 3]),
+            "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap(),
+            56,
+            1,
+        );
+    }
+
+    #[test]
+    fn test_pauser_role_can_pause() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.grant_role(accounts(2), Role::Pauser);
+        assert_eq!(contract.get_roles(accounts(2)), vec![Role::Pauser]);
+        assert_eq!(contract.get_roles(accounts(0)).len(), 3);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.admin_pause_intent(intent_id.clone());
+        contract.set_paused(true);
+        assert!(contract.paused);
+        assert!(matches!(contract.get_intent(intent_id).unwrap().status, IntentStatus::Paused));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only contract owner or FeeManager role can call this method")]
+    fn test_pauser_role_cannot_change_fees() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.grant_role(accounts(2), Role::Pauser);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.set_fee_basis_points(100);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only contract owner or Pauser role can call this method")]
+    fn test_revoked_role_loses_access() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.grant_role(accounts(2), Role::Pauser);
+        contract.grant_role(accounts(2), Role::FeeManager);
+        contract.revoke_role(accounts(2), Role::Pauser);
+        assert_eq!(contract.get_roles(accounts(2)), vec![Role::FeeManager]);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.set_fee_basis_points(100);
+        contract.set_paused(true);
+    }
+
+    #[test]
+    fn test_delegated_roles_cover_admin_methods() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.grant_role(accounts(2), Role::Admin);
+        contract.grant_role(accounts(3), Role::FeeManager);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.block_account(accounts(5));
+        contract.add_allowed_token(accounts(4), U128(0));
+        contract.add_supported_chain(1);
+        contract.set_reserve_ratio_bps(1_000);
+        assert!(contract.is_blocked(accounts(5)));
+        assert_eq!(contract.reserve_ratio_bps, 1_000);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.set_treasury(accounts(3));
+        assert_eq!(contract.get_treasury(), accounts(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only contract owner can call this method")]
+    fn test_admin_role_cannot_emergency_withdraw() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.grant_role(accounts(2), Role::Admin);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let _ = contract.owner_emergency_withdraw(U128(1), accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only contract owner can call this method")]
+    fn test_grant_role_requires_owner() {
+        let context = get_context(accounts(2));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.grant_role(accounts(2), Role::Admin);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only contract owner or Admin role can call this method")]
+    fn test_add_supported_chain_requires_admin() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.add_supported_chain(1);
+    }
+
+    #[test]
+    fn test_get_user_roi() {
+        let mut context = get_context(accounts(1));
+        context.attached_deposit(NearToken::from_near(2));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        assert_eq!(contract.get_user_roi(accounts(1)), "0");
+
+        create(&mut contract, "ETH/USDC", "1.0");
+        assert_eq!(contract.get_user_roi(accounts(1)), "0");
+
+        // 0.5 NEAR profit on 2 NEAR deposited = 25%
+        let half_near = U128(NearToken::from_millinear(500).as_yoctonear());
+        contract.user_profits.insert(&(accounts(1), accounts(0)), &half_near);
+        assert_eq!(contract.get_user_roi(accounts(1)), "2500");
+
+        // A second 2 NEAR deposit halves the return
+        create(&mut contract, "NEAR/USDC", "1.0");
+        assert_eq!(contract.get_user_roi(accounts(1)), "1250");
+    }
+
+    #[test]
+    fn test_circuit_breaker_pauses_on_large_outflow() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_circuit_breaker_threshold(U128(1_500));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.credit_claimable_profit(&accounts(1), &env::current_account_id(), 1_000);
+        assert_eq!(contract.withdraw_all_profits().len(), 1);
+        assert!(!contract.paused);
+
+        contract.credit_claimable_profit(&accounts(1), &env::current_account_id(), 1_000);
+        assert_eq!(contract.withdraw_all_profits().len(), 1);
+        assert!(contract.paused);
+
+        // Only the owner can lift the pause
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_paused(false);
+        assert!(!contract.paused);
+    }
+
+    #[test]
+    fn test_circuit_breaker_window_rolls_over() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_circuit_breaker_threshold(U128(1_500));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.credit_claimable_profit(&accounts(1), &env::current_account_id(), 1_000);
+        contract.withdraw_all_profits();
+
+        testing_env!(context.block_timestamp(NANOS_PER_DAY).build());
+        contract.credit_claimable_profit(&accounts(1), &env::current_account_id(), 1_000);
+        contract.withdraw_all_profits();
+        assert!(!contract.paused);
+    }
+
+    #[test]
+    fn test_blocked_account_cannot_execute() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        let intent_id = create(&mut contract, "ETH/USDC", "1.0");
+        contract.add_keeper(intent_id.clone(), accounts(2));
+        contract.set_owner_only_execution(intent_id.clone(), false);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.block_account(accounts(2));
+        assert!(contract.is_blocked(accounts(2)));
+        assert!(!contract.is_blocked(accounts(1)));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        }));
+        assert!(result.is_err());
+
+        // The intent owner isn't affected, and unblocking restores the keeper
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.unblock_account(accounts(2));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        execute(&mut contract, &intent_id, "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_execution_history(accounts(1)).len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account bob is blocked")]
+    fn test_blocked_account_cannot_create_intent() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.block_account(accounts(1));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        create(&mut contract, "ETH/USDC", "1.0");
+    }
+
+    #[test]
+    #[should_panic(expected = "Account bob is blocked")]
+    fn test_blocked_account_cannot_withdraw() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.credit_claimable_profit(&accounts(1), &env::current_account_id(), 1_000);
+        contract.block_account(accounts(1));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.withdraw_all_profits();
+    }
+
+    /// A contract holding 40 NEAR against 60 NEAR of claimable profit, split
+    /// 50/10 between accounts(1) and accounts(2), with a 50% reserve ratio.
+    fn undercollateralized_contract(context: &mut VMContextBuilder) -> ArbitrageContract {
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .account_balance(NearToken::from_near(40))
+            .build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_reserve_ratio_bps(5_000);
+        let native = env::current_account_id();
+        let near = NearToken::from_near(1).as_yoctonear();
+        contract.credit_claimable_profit(&accounts(1), &native, 50 * near);
+        contract.credit_claimable_profit(&accounts(2), &native, 10 * near);
+        contract
+    }
+
+    #[test]
+    fn test_withdrawal_within_reserve() {
+        let mut context = get_context(accounts(0));
+        let mut contract = undercollateralized_contract(&mut context);
+
+        // 30 NEAR left against 50 NEAR still owed covers the 25 NEAR reserve
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        assert_eq!(contract.withdraw_all_profits().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "below the required reserve")]
+    fn test_withdrawal_breaching_reserve_rejected() {
+        let mut context = get_context(accounts(0));
+        let mut contract = undercollateralized_contract(&mut context);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.withdraw_all_profits();
+    }
+
+    #[test]
+    #[should_panic(expected = "Reserve ratio cannot exceed 100%")]
+    fn test_reserve_ratio_capped() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_reserve_ratio_bps(10_001);
+    }
+
+    #[test]
+    fn test_can_withdraw() {
+        let mut context = get_context(accounts(0));
+        let mut contract = undercollateralized_contract(&mut context);
+        let near = NearToken::from_near(1).as_yoctonear();
+
+        assert!(contract.can_withdraw(accounts(2), U128(10 * near)));
+        assert!(!contract.can_withdraw(accounts(2), U128(11 * near)));
+        assert!(!contract.can_withdraw(accounts(3), U128(1)));
+
+        // accounts(1) is owed 50 NEAR, but the contract only holds 40
+        assert!(!contract.can_withdraw(accounts(1), U128(50 * near)));
+        // 30 NEAR would leave 10 against the 15 NEAR reserve on 30 still owed
+        assert!(!contract.can_withdraw(accounts(1), U128(30 * near)));
+        assert!(contract.can_withdraw(accounts(1), U128(20 * near)));
+
+        contract.block_account(accounts(2));
+        assert!(!contract.can_withdraw(accounts(2), U128(10 * near)));
+        contract.set_paused(true);
+        assert!(!contract.can_withdraw(accounts(1), U128(20 * near)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn test_paused_contract_rejects_withdrawals() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = ArbitrageContract::new(accounts(0));
+        contract.set_paused(true);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.withdraw_all_profits();
+    }
+
+    #[test]
+    fn test_withdraw_all_profits_across_tokens() {
+        let mut context = get_context(accounts(3));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        allow_token(&mut contract, accounts(3));
+        // accounts(3) acts as the NEP-141 token contract funding intent 1
+        contract.ft_on_transfer(
+            accounts(1),
+            U128(1_000),
+            r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.0"}"#.to_string(),
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        execute(&mut contract, "1", "3000.0", "2950.0", U128(1_000));
+        // Native profit is pushed on settlement; only older balances are claimable
+        contract.credit_claimable_profit(&accounts(1), &env::current_account_id(), 1_000);
+
+        let native = env::current_account_id();
+        assert!(contract.get_claimable_profit(accounts(1), native.clone()).0 > 0);
+        assert!(contract.get_claimable_profit(accounts(1), accounts(3)).0 > 0);
+
+        let native_claimable = contract.get_claimable_profit(accounts(1), native.clone());
+        let token_claimable = contract.get_claimable_profit(accounts(1), accounts(3));
+        let promises = contract.withdraw_all_profits();
+        assert_eq!(promises.len(), 2);
+        drop(promises);
+        assert_eq!(contract.get_claimable_profit(accounts(1), native.clone()), U128(0));
+        assert_eq!(contract.get_claimable_profit(accounts(1), accounts(3)), U128(0));
+        assert!(contract.withdraw_all_profits().is_empty());
+
+        // Each payout emits its event once it resolves
+        callback_context(&mut context, PromiseResult::Successful(Vec::new()));
+        for (token_id, amount) in [(accounts(3), token_claimable), (native, native_claimable)] {
+            assert!(contract.on_withdraw_resolved(accounts(1), token_id.clone(), amount));
+            let event = event_payload(get_logs().last().unwrap());
+            assert_eq!(event["event"], "profit_withdrawn");
+            assert_eq!(event["data"][0]["account_id"], accounts(1).to_string());
+            assert_eq!(event["data"][0]["token_id"], token_id.to_string());
+            assert_eq!(event["data"][0]["amount"], amount.0.to_string());
+            assert_eq!(event["data"][0]["remaining"], "0");
+        }
+    }
+
+    #[test]
+    fn test_profits_tracked_per_token() {
+        let mut context = get_context(accounts(3));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        allow_token(&mut contract, accounts(3));
+        allow_token(&mut contract, accounts(4));
+        // accounts(3) and accounts(4) act as two NEP-141 token contracts
+        let msg = r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.0"}"#;
+        contract.ft_on_transfer(accounts(1), U128(1_000), msg.to_string());
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.ft_on_transfer(accounts(1), U128(1_000), msg.to_string());
+
+        // Token intents settle without a swap, so no settlement credits token
+        // profit yet
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        execute(&mut contract, "1", "3000.0", "2950.0", U128(1_000));
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(3)), U128(0));
+
+        contract.add_user_profit(&accounts(1), &accounts(3), 40);
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(3)), U128(40));
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(4)), U128(0));
+
+        contract.add_user_profit(&accounts(1), &accounts(4), 20);
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(4)), U128(20));
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(3)), U128(40));
+        assert_eq!(contract.get_total_profit(accounts(1), accounts(0)), U128(0));
+    }
+
+    #[test]
+    fn test_total_profit_all_tokens() {
+        let mut context = get_context(accounts(3));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        allow_token(&mut contract, accounts(3));
+        allow_token(&mut contract, accounts(4));
+        let msg = r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.0"}"#;
+        contract.ft_on_transfer(accounts(1), U128(1_000), msg.to_string());
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.ft_on_transfer(accounts(1), U128(1_000), msg.to_string());
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert!(contract.get_total_profit_all_tokens(accounts(1)).is_empty());
+        contract.add_user_profit(&accounts(1), &accounts(3), 40);
+        contract.add_user_profit(&accounts(1), &accounts(4), 20);
+        // A second profit in the same token doesn't list it twice
+        contract.add_user_profit(&accounts(1), &accounts(4), 20);
+
+        let profits = contract.get_total_profit_all_tokens(accounts(1));
+        assert_eq!(
+            profits,
+            vec![
+                (accounts(3), contract.get_total_profit(accounts(1), accounts(3))),
+                (accounts(4), contract.get_total_profit(accounts(1), accounts(4))),
+            ]
+        );
+        assert!(profits.iter().all(|(_, profit)| profit.0 > 0));
+    }
+
+    #[test]
+    fn test_intent_params_reject_unknown_fields() {
+        let known = r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.0"}"#;
+        assert!(near_sdk::serde_json::from_str::<IntentParams>(known).is_ok());
+
+        let unknown = r#"{"token_pair":"ETH/USDC","min_profit_treshold":"1.0"}"#;
+        let err = near_sdk::serde_json::from_str::<IntentParams>(unknown).err().unwrap();
+        assert!(err.to_string().contains("unknown field `min_profit_treshold`"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid msg")]
+    fn test_ft_on_transfer_rejects_unknown_msg_fields() {
+        let context = get_context(accounts(3));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        allow_token(&mut contract, accounts(3));
+        let msg = r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.0","slippage":"0.5"}"#;
+        contract.ft_on_transfer(accounts(1), U128(500), msg.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid msg")]
+    fn test_ft_on_transfer_rejects_invalid_dex_account() {
+        let context = get_context(accounts(3));
+        testing_env!(context.build());
+
+        let mut contract = ArbitrageContract::new(accounts(0));
+        allow_token(&mut contract, accounts(3));
+        let msg = r#"{"token_pair":"ETH/USDC","min_profit_threshold":"1.0","dex":"Not A DEX!"}"#;
+        contract.ft_on_transfer(accounts(1), U128(500), msg.to_string());
     }
 }
\ No newline at end of file